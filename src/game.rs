@@ -0,0 +1,987 @@
+//! A turn-based rules engine built on the [`hand`](crate::hand) and [`score`](crate::score)
+//! modules: a [`GameState`] tracks one hand of riichi from the deal to its conclusion, and
+//! [`GameState::legal_actions`] / [`GameState::apply`] drive it one [`Action`] at a time,
+//! automatically building the [`WinContext`] a winning action needs.
+//!
+//! This is deliberately not a complete rulebook. Multiple simultaneous ron resolves to whichever
+//! [`Action::Ron`] is applied first rather than awarding every eligible winner, chankan is not
+//! modelled (declaring a kan never opens a response window, so no seat can rob it and a win here
+//! never carries the `CHANKAN` yaku), and abortive/exhaustive draws beyond simply running out of
+//! wall are not modelled. Callers needing those should layer them on top.
+
+use crate::hand::{yaku, CompleteHand, Group, GroupType, Hand, Location, Opponent, WinContext};
+use crate::tile::{Direction, Tile, TileInstance};
+use failure::format_err;
+use failure::Error;
+
+/// A single decision point in the turn order.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[allow(missing_docs)]
+pub enum Action {
+    Draw,
+    Discard(TileInstance),
+    /// Call chi on the current discard, forming the given sequence.
+    Chi(Group),
+    /// Call pon on the current discard, forming the given triplet.
+    Pon(Group),
+    /// Kan: a daiminkan on the current discard, a shouminkan upgrading an open triplet, or an
+    /// ankan declared on one's own turn, distinguished by the shape of the [`Group`] itself.
+    Kan(Group),
+    /// Discard a tile while declaring riichi.
+    Riichi(TileInstance),
+    /// Win off one's own draw.
+    Tsumo,
+    /// Win off the current discard.
+    Ron,
+    /// Decline to call or ron on the current discard.
+    Pass,
+}
+
+/// The state machine's current decision point.
+#[derive(Debug, Clone)]
+enum Phase {
+    /// `0` must draw.
+    AwaitingDraw(Direction),
+    /// `0` has drawn and must discard, riichi, kan, or tsumo.
+    AwaitingDiscard(Direction),
+    /// `discarder` just discarded `discard`; every other seat not already in `passed` may call,
+    /// ron, or pass.
+    AwaitingResponse {
+        discarder: Direction,
+        discard: TileInstance,
+        passed: Vec<Direction>,
+    },
+    /// The hand has ended, by a win or by the wall running out.
+    Over,
+}
+
+/// The outcome of a winning [`Action::Tsumo`] or [`Action::Ron`]: every legal decomposition of the
+/// winning hand that carries a yaku, ready to be scored with [`crate::score::score`].
+#[derive(Debug, Clone)]
+pub struct WinResult {
+    /// The seat that won.
+    pub winner: Direction,
+    /// Every legal decomposition of the winning fourteen tiles that has at least one yaku.
+    pub hands: Vec<CompleteHand>,
+    /// The context the decompositions and yaku were evaluated against.
+    pub ctx: WinContext,
+    /// The winning fourteen tiles, carrying the red-five information `hands` itself discards, for
+    /// [`crate::score::score`]'s dora counting.
+    pub instances: Vec<TileInstance>,
+}
+
+/// The full state of one hand of riichi mahjong in progress.
+#[derive(Debug, Clone)]
+pub struct GameState {
+    hands: [Hand; 4],
+    wall: Vec<TileInstance>,
+    dead_wall: Vec<TileInstance>,
+    dora_indicators: Vec<Tile>,
+    ura_indicators: Vec<Tile>,
+    // Ura dora indicators not yet revealed: one is turned up (into `ura_indicators`) alongside
+    // each kan dora indicator, but hidden from `win_context` unless the winner had riichi.
+    ura_dora: Vec<Tile>,
+    discards: [Vec<Tile>; 4],
+    round: Direction,
+    honba: u8,
+    riichi_sticks: u32,
+    riichi: [bool; 4],
+    first_turn: [bool; 4],
+    double_riichi: [bool; 4],
+    no_calls_yet: bool,
+    wall_empty: bool,
+    last_drawn: Option<(TileInstance, Location)>,
+    last_win: Option<WinResult>,
+    phase: Phase,
+}
+
+fn seat_index(d: Direction) -> usize {
+    match d {
+        Direction::East => 0,
+        Direction::South => 1,
+        Direction::West => 2,
+        Direction::North => 3,
+    }
+}
+
+/// Returns how `seat` sees `other`, for call eligibility: the player immediately before you in
+/// turn order (your kamicha) is [`Opponent::Right`], the one across is [`Opponent::Across`], and
+/// the one immediately after you (your shimocha) is [`Opponent::Left`].
+fn relative_opponent(other: Direction, seat: Direction) -> Opponent {
+    match (seat_index(seat) + 4 - seat_index(other)) % 4 {
+        1 => Opponent::Right,
+        2 => Opponent::Across,
+        3 => Opponent::Left,
+        _ => unreachable!("a seat cannot discard to itself"),
+    }
+}
+
+/// Returns `true` if no group of the hand was called.
+fn is_closed(hand: &Hand) -> bool {
+    hand.groups().iter().all(|g| !g.is_open())
+}
+
+/// Returns every distinct tile instance in `instances`, in first-seen order. A red five and an
+/// ordinary one of the same value count as distinct, since discarding or riichi-ing on one is a
+/// different choice from the other.
+fn dedup_instances(instances: &[TileInstance]) -> Vec<TileInstance> {
+    let mut out: Vec<TileInstance> = Vec::new();
+    for &i in instances {
+        if !out.contains(&i) {
+            out.push(i);
+        }
+    }
+    out
+}
+
+/// Returns `true` if discarding `discard` from `hand` would leave it in tenpai.
+fn reaches_tenpai(hand: &Hand, discard: Tile) -> bool {
+    let mut h = hand.clone();
+    h.remove_loose(&[discard]) && h.shanten() == 0
+}
+
+impl GameState {
+    /// Starts a new hand. `hands` are the four starting thirteen-tile hands, indexed by seat wind
+    /// (East/South/West/North); `wall` is the live wall with the next draw at the end, so that
+    /// draws simply pop it; `dead_wall` interleaves kan replacement draws with the kan dora
+    /// indicators that follow them, from the back, so that each kan pops one of each; and
+    /// `dora_indicators` are the indicator(s) already turned up at the start of the hand.
+    /// `ura_dora` are the corresponding ura dora indicators, in the same back-to-front order: the
+    /// last one is turned up immediately (though, like every ura indicator, only ever shown to a
+    /// riichi winner), and one more turns up alongside each kan dora indicator. Play begins with
+    /// East's draw.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        hands: [Hand; 4],
+        wall: Vec<TileInstance>,
+        dead_wall: Vec<TileInstance>,
+        dora_indicators: Vec<Tile>,
+        mut ura_dora: Vec<Tile>,
+        round: Direction,
+        honba: u8,
+        riichi_sticks: u32,
+    ) -> GameState {
+        let ura_indicators = ura_dora.pop().into_iter().collect();
+        GameState {
+            hands,
+            wall,
+            dead_wall,
+            dora_indicators,
+            ura_indicators,
+            ura_dora,
+            discards: [Vec::new(), Vec::new(), Vec::new(), Vec::new()],
+            round,
+            honba,
+            riichi_sticks,
+            riichi: [false; 4],
+            first_turn: [true; 4],
+            double_riichi: [false; 4],
+            no_calls_yet: true,
+            wall_empty: false,
+            last_drawn: None,
+            last_win: None,
+            phase: Phase::AwaitingDraw(Direction::East),
+        }
+    }
+
+    /// The hand currently held by `seat`.
+    pub fn hand(&self, seat: Direction) -> &Hand {
+        &self.hands[seat_index(seat)]
+    }
+
+    /// The tiles `seat` has discarded so far, in order.
+    pub fn discards(&self, seat: Direction) -> &[Tile] {
+        &self.discards[seat_index(seat)]
+    }
+
+    /// The dora indicators currently turned up, live wall and kan dora alike.
+    pub fn dora_indicators(&self) -> &[Tile] {
+        &self.dora_indicators
+    }
+
+    /// The number of honba currently on the table.
+    pub fn honba(&self) -> u8 {
+        self.honba
+    }
+
+    /// The number of riichi sticks currently on the table.
+    pub fn riichi_sticks(&self) -> u32 {
+        self.riichi_sticks
+    }
+
+    /// Returns `true` if `seat` has declared riichi.
+    pub fn is_riichi(&self, seat: Direction) -> bool {
+        self.riichi[seat_index(seat)]
+    }
+
+    /// The outcome of the win that ended the hand, if it ended in one rather than a draw.
+    pub fn last_win(&self) -> Option<&WinResult> {
+        self.last_win.as_ref()
+    }
+
+    fn win_context(&self, seat: Direction, agari: Tile, source: Location) -> WinContext {
+        let idx = seat_index(seat);
+        WinContext {
+            agari,
+            source,
+            riichi: self.riichi[idx],
+            first_turn: self.first_turn[idx],
+            double_riichi: self.riichi[idx] && self.double_riichi[idx],
+            wall_empty: self.wall_empty,
+            round: self.round,
+            seat,
+            honba: self.honba,
+            dora_indicators: self.dora_indicators.clone(),
+            ura_indicators: if self.riichi[idx] {
+                self.ura_indicators.clone()
+            } else {
+                Vec::new()
+            },
+        }
+    }
+
+    /// Returns `true` if some seat other than `seat` and the discarder hasn't yet passed on the
+    /// current discard and holds a strictly higher-priority response to it: ron always outranks
+    /// every call, and pon/kan outrank chi. `false` outside [`Phase::AwaitingResponse`].
+    fn blocked_by_priority(&self, seat: Direction, is_chi: bool) -> bool {
+        let (discarder, discard, passed) = match &self.phase {
+            Phase::AwaitingResponse { discarder, discard, passed } => (*discarder, *discard, passed),
+            _ => return false,
+        };
+        [Direction::East, Direction::South, Direction::West, Direction::North]
+            .iter()
+            .copied()
+            .filter(|other| *other != seat && *other != discarder && !passed.contains(other))
+            .any(|other| {
+                let opp = relative_opponent(discarder, other);
+                let hand = &self.hands[seat_index(other)];
+                let mut test_hand = hand.clone();
+                test_hand.draw(discard);
+                if self
+                    .winning_hands(&test_hand, other, discard.tile, Location::Discard(opp))
+                    .is_some()
+                {
+                    return true;
+                }
+                is_chi && hand.possible_calls(discard.tile, opp).iter().any(|g| g.ty() != GroupType::Sequence)
+            })
+    }
+
+    /// Returns every legal decomposition of `hand` with `agari` won from `source` that carries a
+    /// yaku, and the context they were evaluated against. `None` if the hand cannot legally win.
+    fn winning_hands(
+        &self,
+        hand: &Hand,
+        seat: Direction,
+        agari: Tile,
+        source: Location,
+    ) -> Option<(Vec<CompleteHand>, WinContext)> {
+        let ctx = self.win_context(seat, agari, source);
+        let winners: Vec<CompleteHand> = hand
+            .decompositions(&ctx)
+            .into_iter()
+            .filter(|h| !yaku::detect(h, &ctx).is_empty())
+            .collect();
+        if winners.is_empty() {
+            None
+        } else {
+            Some((winners, ctx))
+        }
+    }
+
+    /// Returns every action `seat` may legally take right now; empty if it isn't their turn to
+    /// act. Note that a response phase never opens following a kan (see the module docs), so a
+    /// robbed kan is never among the actions offered here.
+    pub fn legal_actions(&self, seat: Direction) -> Vec<Action> {
+        match &self.phase {
+            Phase::AwaitingDraw(cur) if *cur == seat => vec![Action::Draw],
+            Phase::AwaitingDiscard(cur) if *cur == seat => self.discard_phase_actions(seat),
+            Phase::AwaitingResponse { discarder, discard, passed }
+                if *discarder != seat && !passed.contains(&seat) =>
+            {
+                self.response_phase_actions(seat, *discarder, *discard)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn discard_phase_actions(&self, seat: Direction) -> Vec<Action> {
+        let hand = &self.hands[seat_index(seat)];
+        let mut actions = Vec::new();
+        if let Some((drawn, source)) = self.last_drawn {
+            if self.winning_hands(hand, seat, drawn.tile, source).is_some() {
+                actions.push(Action::Tsumo);
+            }
+        }
+        if is_closed(hand) && !self.riichi[seat_index(seat)] {
+            actions.extend(
+                dedup_instances(&hand.loose_instances())
+                    .into_iter()
+                    .filter(|i| reaches_tenpai(hand, i.tile))
+                    .map(Action::Riichi),
+            );
+        }
+        // An added kan here never opens a chankan response window for the other seats (see the
+        // module docs) — `apply_kan_action` reopens `AwaitingDiscard` for `seat` directly, so robbing a
+        // kan is unreachable through this engine as shipped.
+        actions.extend(hand.closed_kan().into_iter().map(Action::Kan));
+        actions.extend(hand.added_kan().into_iter().map(Action::Kan));
+        actions.extend(dedup_instances(&hand.loose_instances()).into_iter().map(Action::Discard));
+        actions
+    }
+
+    fn response_phase_actions(&self, seat: Direction, discarder: Direction, discard: TileInstance) -> Vec<Action> {
+        let hand = &self.hands[seat_index(seat)];
+        let mut actions = vec![Action::Pass];
+        let opp = relative_opponent(discarder, seat);
+        actions.extend(
+            hand.possible_calls(discard.tile, opp)
+                .into_iter()
+                .map(|g| match g.ty() {
+                    GroupType::Sequence => Action::Chi(g),
+                    GroupType::Triplet => Action::Pon(g),
+                    GroupType::Quad => Action::Kan(g),
+                })
+                .filter(|a| !self.blocked_by_priority(seat, matches!(a, Action::Chi(_)))),
+        );
+        let mut test_hand = hand.clone();
+        test_hand.draw(discard);
+        if self
+            .winning_hands(&test_hand, seat, discard.tile, Location::Discard(opp))
+            .is_some()
+        {
+            actions.push(Action::Ron);
+        }
+        actions
+    }
+
+    /// Applies `action` as `seat`'s move, returning the resulting state. Errors if the action
+    /// isn't legal for `seat` right now, whether because of the current phase or because the hand
+    /// doesn't actually hold what the action claims; callers should generally restrict themselves
+    /// to what [`legal_actions`](GameState::legal_actions) returns, but `apply` re-validates
+    /// regardless.
+    pub fn apply(&self, seat: Direction, action: Action) -> Result<GameState, Error> {
+        let mut next = self.clone();
+        match action {
+            Action::Draw => next.apply_draw(seat)?,
+            Action::Discard(t) => next.apply_discard(seat, t)?,
+            Action::Riichi(t) => next.apply_riichi(seat, t)?,
+            Action::Kan(g) => next.apply_kan_action(seat, g)?,
+            Action::Tsumo => next.apply_tsumo(seat)?,
+            Action::Ron => next.apply_ron(seat)?,
+            Action::Chi(g) | Action::Pon(g) => next.apply_call(seat, g)?,
+            Action::Pass => next.apply_pass(seat)?,
+        }
+        Ok(next)
+    }
+
+    fn apply_draw(&mut self, seat: Direction) -> Result<(), Error> {
+        match self.phase {
+            Phase::AwaitingDraw(cur) if cur == seat => {}
+            _ => return Err(format_err!("{:?} may not draw right now", seat)),
+        }
+        let tile = self.wall.pop().ok_or_else(|| format_err!("the wall is empty"))?;
+        if self.wall.is_empty() {
+            self.wall_empty = true;
+        }
+        self.hands[seat_index(seat)].draw(tile);
+        self.last_drawn = Some((tile, Location::LiveWall));
+        self.phase = Phase::AwaitingDiscard(seat);
+        Ok(())
+    }
+
+    fn apply_discard(&mut self, seat: Direction, tile: TileInstance) -> Result<(), Error> {
+        match self.phase {
+            Phase::AwaitingDiscard(cur) if cur == seat => {}
+            _ => return Err(format_err!("{:?} may not discard right now", seat)),
+        }
+        if !self.hands[seat_index(seat)].remove_for_discard(tile) {
+            return Err(format_err!("{:?} does not hold {}", seat, tile.tile));
+        }
+        self.discards[seat_index(seat)].push(tile.tile);
+        self.first_turn[seat_index(seat)] = false;
+        self.phase = Phase::AwaitingResponse { discarder: seat, discard: tile, passed: Vec::new() };
+        Ok(())
+    }
+
+    fn apply_riichi(&mut self, seat: Direction, tile: TileInstance) -> Result<(), Error> {
+        match self.phase {
+            Phase::AwaitingDiscard(cur) if cur == seat => {}
+            _ => return Err(format_err!("{:?} may not declare riichi right now", seat)),
+        }
+        if self.riichi[seat_index(seat)] {
+            return Err(format_err!("{:?} has already declared riichi", seat));
+        }
+        let hand = &self.hands[seat_index(seat)];
+        if !is_closed(hand) || !reaches_tenpai(hand, tile.tile) {
+            return Err(format_err!("discarding {} would not leave {:?} in tenpai", tile.tile, seat));
+        }
+        self.double_riichi[seat_index(seat)] = self.discards[seat_index(seat)].is_empty() && self.no_calls_yet;
+        self.apply_discard(seat, tile)?;
+        let idx = seat_index(seat);
+        self.riichi[idx] = true;
+        self.riichi_sticks += 1;
+        // `apply_discard` just closed out `seat`'s first-turn window; reopen it, now standing for
+        // the ippatsu window this riichi just started.
+        self.first_turn[idx] = true;
+        Ok(())
+    }
+
+    fn apply_kan_action(&mut self, seat: Direction, group: Group) -> Result<(), Error> {
+        match self.phase.clone() {
+            Phase::AwaitingDiscard(cur) if cur == seat => {
+                if group.is_open() && !group.is_added() {
+                    return Err(format_err!(
+                        "{:?} may only ankan or shouminkan on their own turn, not daiminkan",
+                        seat
+                    ));
+                }
+                self.apply_kan(seat, group, None)?;
+                self.first_turn = [false; 4];
+                self.no_calls_yet = false;
+                self.phase = Phase::AwaitingDiscard(seat);
+            }
+            Phase::AwaitingResponse { discarder, discard, .. } if discarder != seat => {
+                if !group.is_open() || group.is_added() {
+                    return Err(format_err!(
+                        "{:?} may only daiminkan off another player's discard, not ankan or shouminkan",
+                        seat
+                    ));
+                }
+                if group.tiles().last() != Some(&discard.tile) {
+                    return Err(format_err!("{:?}'s kan does not match the current discard", seat));
+                }
+                if group.off() != Some(relative_opponent(discarder, seat)) {
+                    return Err(format_err!("{:?}'s kan is not recorded as called off {:?}", seat, discarder));
+                }
+                if self.blocked_by_priority(seat, false) {
+                    return Err(format_err!(
+                        "{:?}'s kan is pre-empted by another player's higher-priority ron",
+                        seat
+                    ));
+                }
+                self.apply_kan(seat, group, Some(discard))?;
+                self.first_turn = [false; 4];
+                self.no_calls_yet = false;
+                self.phase = Phase::AwaitingDiscard(seat);
+            }
+            _ => return Err(format_err!("{:?} may not kan right now", seat)),
+        }
+        Ok(())
+    }
+
+    /// Mutates `seat`'s hand to apply an ankan, shouminkan, or daiminkan (distinguished by
+    /// `group`'s own shape), then draws the rinshan replacement tile and reveals the kan dora
+    /// indicator that follows it in the dead wall. `called` is the claimed discard for a
+    /// daiminkan, carrying its own red-five status; `None` for an ankan or shouminkan, which only
+    /// ever draw on tiles already in the hand.
+    fn apply_kan(&mut self, seat: Direction, group: Group, called: Option<TileInstance>) -> Result<(), Error> {
+        let idx = seat_index(seat);
+        if group.is_added() {
+            let new_tile = *group.tiles().last().expect("a kan has tiles");
+            if !self.hands[idx].remove_loose(&[new_tile]) {
+                return Err(format_err!("{:?} does not hold {} to add to the kan", seat, new_tile));
+            }
+            if !self.hands[idx].upgrade_triplet(group) {
+                return Err(format_err!("{:?} has no open triplet to upgrade to a kan", seat));
+            }
+        } else if group.is_open() {
+            let mut needed = group.tiles().to_vec();
+            needed.pop();
+            if !self.hands[idx].remove_loose(&needed) {
+                return Err(format_err!("{:?} does not hold enough {} for a kan", seat, group.first_tile()));
+            }
+            let called = called.expect("a daiminkan is always called off a discard");
+            self.hands[idx].add_called_group(group, called);
+        } else {
+            if !self.hands[idx].remove_loose(group.tiles()) {
+                return Err(format_err!("{:?} does not hold four {} for a closed kan", seat, group.first_tile()));
+            }
+            self.hands[idx].add_group(group);
+        }
+        let replacement = self.dead_wall.pop().ok_or_else(|| format_err!("the dead wall is empty"))?;
+        self.hands[idx].draw(replacement);
+        self.last_drawn = Some((replacement, Location::DeadWall));
+        if let Some(indicator) = self.dead_wall.pop() {
+            self.dora_indicators.push(indicator.tile);
+            if let Some(ura) = self.ura_dora.pop() {
+                self.ura_indicators.push(ura);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_call(&mut self, seat: Direction, group: Group) -> Result<(), Error> {
+        let (discarder, discard) = match self.phase {
+            Phase::AwaitingResponse { discarder, discard, .. } if discarder != seat => (discarder, discard),
+            _ => return Err(format_err!("{:?} may not call right now", seat)),
+        };
+        if group.tiles().last() != Some(&discard.tile) {
+            return Err(format_err!("{:?}'s call does not match the current discard", seat));
+        }
+        if group.off() != Some(relative_opponent(discarder, seat)) {
+            return Err(format_err!("{:?}'s call is not recorded as called off {:?}", seat, discarder));
+        }
+        let is_chi = group.ty() == GroupType::Sequence;
+        if is_chi && group.off() != Some(Opponent::Right) {
+            return Err(format_err!("{:?} may only chi off the player immediately before them", seat));
+        }
+        if self.blocked_by_priority(seat, is_chi) {
+            return Err(format_err!(
+                "{:?}'s call is pre-empted by another player's higher-priority ron, pon, or kan",
+                seat
+            ));
+        }
+        let mut needed = group.tiles().to_vec();
+        needed.pop();
+        if !self.hands[seat_index(seat)].remove_loose(&needed) {
+            return Err(format_err!("{:?} does not hold the tiles for that call", seat));
+        }
+        self.hands[seat_index(seat)].add_called_group(group, discard);
+        self.first_turn = [false; 4];
+        self.no_calls_yet = false;
+        self.phase = Phase::AwaitingDiscard(seat);
+        Ok(())
+    }
+
+    fn apply_tsumo(&mut self, seat: Direction) -> Result<(), Error> {
+        match self.phase {
+            Phase::AwaitingDiscard(cur) if cur == seat => {}
+            _ => return Err(format_err!("{:?} may not tsumo right now", seat)),
+        }
+        let (drawn, source) = self.last_drawn.ok_or_else(|| format_err!("no drawn tile to win on"))?;
+        let hand = self.hands[seat_index(seat)].clone();
+        let (hands, ctx) = self
+            .winning_hands(&hand, seat, drawn.tile, source)
+            .ok_or_else(|| format_err!("{:?}'s hand has no yaku", seat))?;
+        let instances = hand.instances();
+        self.last_win = Some(WinResult { winner: seat, hands, ctx, instances });
+        self.phase = Phase::Over;
+        Ok(())
+    }
+
+    fn apply_ron(&mut self, seat: Direction) -> Result<(), Error> {
+        let (discarder, discard) = match self.phase {
+            Phase::AwaitingResponse { discarder, discard, .. } if discarder != seat => (discarder, discard),
+            _ => return Err(format_err!("{:?} may not ron right now", seat)),
+        };
+        let mut hand = self.hands[seat_index(seat)].clone();
+        hand.draw(discard);
+        let opp = relative_opponent(discarder, seat);
+        let (hands, ctx) = self
+            .winning_hands(&hand, seat, discard.tile, Location::Discard(opp))
+            .ok_or_else(|| format_err!("{:?}'s hand has no yaku", seat))?;
+        let instances = hand.instances();
+        self.last_win = Some(WinResult { winner: seat, hands, ctx, instances });
+        self.phase = Phase::Over;
+        Ok(())
+    }
+
+    fn apply_pass(&mut self, seat: Direction) -> Result<(), Error> {
+        let next_drawer = match &mut self.phase {
+            Phase::AwaitingResponse { discarder, passed, .. } if *discarder != seat => {
+                if !passed.contains(&seat) {
+                    passed.push(seat);
+                }
+                if passed.len() == 3 {
+                    Some(discarder.next())
+                } else {
+                    None
+                }
+            }
+            _ => return Err(format_err!("{:?} may not pass right now", seat)),
+        };
+        if let Some(d) = next_drawer {
+            self.phase = if self.wall.is_empty() { Phase::Over } else { Phase::AwaitingDraw(d) };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hand::Hand;
+    use crate::tile::parse_tiles;
+
+    fn hand(notation: &str) -> Hand {
+        Hand::new(parse_tiles(notation).unwrap())
+    }
+
+    fn tile(notation: &str) -> TileInstance {
+        TileInstance::new(parse_tiles(notation).unwrap()[0])
+    }
+
+    fn round_of_passes(state: &GameState, discarder: Direction) -> GameState {
+        let mut state = state.clone();
+        let mut seat = discarder.next();
+        while seat != discarder {
+            state = state.apply(seat, Action::Pass).unwrap();
+            seat = seat.next();
+        }
+        state
+    }
+
+    /// A riichi declared on a player's very first discard, won on an uninterrupted tsumo right
+    /// after, is both a double riichi and an ippatsu: the two yaku have different legality
+    /// conditions even though they read off the same turn order.
+    #[test]
+    fn riichi_on_the_first_discard_is_both_double_riichi_and_ippatsu() {
+        let hands = [
+            hand("111222333m44p55p"),
+            hand("111222333m66p77p"),
+            hand("111222333m88p99p"),
+            hand("111222333m11z22z"),
+        ];
+        // Drawn in this order: East's first draw, South, West, North, then East's winning draw.
+        // `GameState::new` draws from the end of `wall`, so list them back to front.
+        let wall = vec![tile("4p"), tile("7m"), tile("8m"), tile("9m"), tile("9s")];
+        let state = GameState::new(hands, wall, Vec::new(), Vec::new(), Vec::new(), Direction::East, 0, 0);
+
+        let state = state.apply(Direction::East, Action::Draw).unwrap();
+        let state = state.apply(Direction::East, Action::Riichi(tile("9s"))).unwrap();
+        let state = round_of_passes(&state, Direction::East);
+
+        let state = state.apply(Direction::South, Action::Draw).unwrap();
+        let state = state.apply(Direction::South, Action::Discard(tile("9m"))).unwrap();
+        let state = round_of_passes(&state, Direction::South);
+
+        let state = state.apply(Direction::West, Action::Draw).unwrap();
+        let state = state.apply(Direction::West, Action::Discard(tile("8m"))).unwrap();
+        let state = round_of_passes(&state, Direction::West);
+
+        let state = state.apply(Direction::North, Action::Draw).unwrap();
+        let state = state.apply(Direction::North, Action::Discard(tile("7m"))).unwrap();
+        let state = round_of_passes(&state, Direction::North);
+
+        let state = state.apply(Direction::East, Action::Draw).unwrap();
+        let state = state.apply(Direction::East, Action::Tsumo).unwrap();
+
+        let ctx = &state.last_win().unwrap().ctx;
+        assert!(ctx.first_turn);
+        assert!(ctx.double_riichi);
+    }
+
+    /// A riichi declared well into the hand still opens an ippatsu window, but it is not a double
+    /// riichi: the two yaku must not be conflated just because they share the same `riichi` and
+    /// `first_turn` conditions.
+    #[test]
+    fn a_late_riichi_is_ippatsu_eligible_but_not_double_riichi() {
+        let hands = [
+            hand("111222333m44p55p"),
+            hand("111222333m66p77p"),
+            hand("111222333m88p99p"),
+            hand("111222333m11z22z"),
+        ];
+        // Same back-to-front convention as above, covering two full go-arounds before the win.
+        let wall = vec![
+            tile("4p"), tile("7m"), tile("8m"), tile("9m"), tile("9s"),
+            tile("7m"), tile("8m"), tile("9m"), tile("9s"),
+        ];
+        let state = GameState::new(hands, wall, Vec::new(), Vec::new(), Vec::new(), Direction::East, 0, 0);
+
+        // East discards a drawn, irrelevant tile on the very first go-around instead of riichi-ing.
+        let state = state.apply(Direction::East, Action::Draw).unwrap();
+        let state = state.apply(Direction::East, Action::Discard(tile("9s"))).unwrap();
+        let state = round_of_passes(&state, Direction::East);
+
+        let state = state.apply(Direction::South, Action::Draw).unwrap();
+        let state = state.apply(Direction::South, Action::Discard(tile("9m"))).unwrap();
+        let state = round_of_passes(&state, Direction::South);
+
+        let state = state.apply(Direction::West, Action::Draw).unwrap();
+        let state = state.apply(Direction::West, Action::Discard(tile("8m"))).unwrap();
+        let state = round_of_passes(&state, Direction::West);
+
+        let state = state.apply(Direction::North, Action::Draw).unwrap();
+        let state = state.apply(Direction::North, Action::Discard(tile("7m"))).unwrap();
+        let state = round_of_passes(&state, Direction::North);
+
+        // East's second discard is the riichi declaration, on their second go-around.
+        let state = state.apply(Direction::East, Action::Draw).unwrap();
+        let state = state.apply(Direction::East, Action::Riichi(tile("9s"))).unwrap();
+        let state = round_of_passes(&state, Direction::East);
+
+        let state = state.apply(Direction::South, Action::Draw).unwrap();
+        let state = state.apply(Direction::South, Action::Discard(tile("9m"))).unwrap();
+        let state = round_of_passes(&state, Direction::South);
+
+        let state = state.apply(Direction::West, Action::Draw).unwrap();
+        let state = state.apply(Direction::West, Action::Discard(tile("8m"))).unwrap();
+        let state = round_of_passes(&state, Direction::West);
+
+        let state = state.apply(Direction::North, Action::Draw).unwrap();
+        let state = state.apply(Direction::North, Action::Discard(tile("7m"))).unwrap();
+        let state = round_of_passes(&state, Direction::North);
+
+        let state = state.apply(Direction::East, Action::Draw).unwrap();
+        let state = state.apply(Direction::East, Action::Tsumo).unwrap();
+
+        let ctx = &state.last_win().unwrap().ctx;
+        assert!(ctx.first_turn);
+        assert!(!ctx.double_riichi);
+    }
+
+    /// A riichi win sees the ura dora indicators seeded in `GameState::new`: the pool must
+    /// actually be threaded through to `win_context`, not just sit unused alongside the kan dora
+    /// indicators it mirrors.
+    #[test]
+    fn a_riichi_win_reveals_ura_dora_seeded_at_the_start_of_the_hand() {
+        let hands = [
+            hand("111222333m44p55p"), // East: shanpon wait on 4p/5p.
+            hand("111222333m66p77p"),
+            hand("111222333m88p99p"),
+            hand("111222333m11z22z"),
+        ];
+        // Drawn in this order: East's first draw, South, West, North, then East's winning draw.
+        let wall = vec![tile("4p"), tile("7m"), tile("8m"), tile("9m"), tile("9s")];
+        // The indicator "3p" points at "4p", which East's winning shanpon triplet supplies three
+        // copies of.
+        let ura_dora = vec![tile("3p").tile];
+        let state = GameState::new(hands, wall, Vec::new(), Vec::new(), ura_dora, Direction::East, 0, 0);
+
+        let state = state.apply(Direction::East, Action::Draw).unwrap();
+        let state = state.apply(Direction::East, Action::Riichi(tile("9s"))).unwrap();
+        let state = round_of_passes(&state, Direction::East);
+
+        let state = state.apply(Direction::South, Action::Draw).unwrap();
+        let state = state.apply(Direction::South, Action::Discard(tile("9m"))).unwrap();
+        let state = round_of_passes(&state, Direction::South);
+
+        let state = state.apply(Direction::West, Action::Draw).unwrap();
+        let state = state.apply(Direction::West, Action::Discard(tile("8m"))).unwrap();
+        let state = round_of_passes(&state, Direction::West);
+
+        let state = state.apply(Direction::North, Action::Draw).unwrap();
+        let state = state.apply(Direction::North, Action::Discard(tile("7m"))).unwrap();
+        let state = round_of_passes(&state, Direction::North);
+
+        let state = state.apply(Direction::East, Action::Draw).unwrap();
+        let state = state.apply(Direction::East, Action::Tsumo).unwrap();
+
+        let win = state.last_win().unwrap();
+        assert_eq!(win.ctx.ura_indicators, vec![tile("3p").tile]);
+        assert_eq!(crate::tile::dora_matches(&win.instances, &win.ctx.ura_indicators), 3);
+    }
+
+    /// A red five drawn straight from the live wall survives into a tsumo's
+    /// `WinResult::instances`, so `score::score` can count it as aka dora: the whole point of
+    /// threading `TileInstance` through `Hand` and `GameState` rather than stopping at `Tile`.
+    #[test]
+    fn a_red_five_drawn_from_the_wall_reaches_the_win_results_instances() {
+        let hands = [
+            hand("111222333m44p55p"), // East: shanpon wait on 4p/5p.
+            hand("111222333m66p77p"),
+            hand("111222333m88p99p"),
+            hand("111222333m11z22z"),
+        ];
+        let red_5p = TileInstance { tile: tile("5p").tile, red: true };
+        let wall = vec![red_5p];
+        let state = GameState::new(hands, wall, Vec::new(), Vec::new(), Vec::new(), Direction::East, 0, 0);
+
+        let state = state.apply(Direction::East, Action::Draw).unwrap();
+        let state = state.apply(Direction::East, Action::Tsumo).unwrap();
+
+        let win = state.last_win().unwrap();
+        assert!(win.instances.contains(&red_5p));
+
+        let rules = crate::score::Rules::default();
+        let best_han = win
+            .hands
+            .iter()
+            .filter_map(|h| crate::score::score(h, &win.instances, &win.ctx, &rules))
+            .map(|(han, _, _)| han)
+            .max()
+            .expect("the shanpon wait on 5p has a menzen tsumo yaku");
+        // Menzen tsumo (1 han) plus the red five (1 han) is at least 2.
+        assert!(best_han >= 2);
+    }
+
+    /// A ron outranks every call: if another seat can still ron on the current discard, a chi
+    /// offered by a lower-priority seat is neither legal nor listed, even though the tiles for it
+    /// are there. Once that seat passes, the chi opens back up.
+    #[test]
+    fn ron_pre_empts_a_lower_priority_chi() {
+        let hands = [
+            hand("111222333m44p55p"), // East: holds a spare 5p to discard.
+            hand("111222333m46p77p"), // South: could chi 4p5p6p off East.
+            hand("111222333m88s99s"), // West: uninvolved.
+            hand("111222333m44p55p"), // North: tenpai for toitoi, rons on 4p or 5p.
+        ];
+        let wall = vec![tile("9s")];
+        let state = GameState::new(hands, wall, Vec::new(), Vec::new(), Vec::new(), Direction::East, 0, 0);
+
+        let state = state.apply(Direction::East, Action::Draw).unwrap();
+        let state = state.apply(Direction::East, Action::Discard(tile("5p"))).unwrap();
+
+        assert!(!state
+            .legal_actions(Direction::South)
+            .iter()
+            .any(|a| matches!(a, Action::Chi(_))));
+        assert!(state.legal_actions(Direction::North).contains(&Action::Ron));
+
+        let chi = state.hand(Direction::South).possible_calls(tile("5p").tile, Opponent::Right)[0].clone();
+        assert!(state.apply(Direction::South, Action::Chi(chi.clone())).is_err());
+
+        // Once North passes up the ron, nothing outranks the chi any longer.
+        let state = state.apply(Direction::North, Action::Pass).unwrap();
+        assert!(state
+            .legal_actions(Direction::South)
+            .iter()
+            .any(|a| matches!(a, Action::Chi(_))));
+        assert!(state.apply(Direction::South, Action::Chi(chi)).is_ok());
+    }
+
+    /// A kan declared on its own declarer's turn must close the ippatsu window for the whole
+    /// table, just like a call does: an uninterrupted-looking riichi-then-tsumo must not still
+    /// score ippatsu if another seat's ankan happened in between.
+    #[test]
+    fn a_self_declared_kan_breaks_another_players_ippatsu() {
+        let hands = [
+            hand("111222333m44p55p"), // East: shanpon wait on 4p/5p, riichis on the first discard.
+            hand("1111222333m66p7p"), // South: holds four 1m to ankan on its own turn.
+            hand("111222333m88p99p"),
+            hand("111222333m11z22z"),
+        ];
+        // Same back-to-front convention as the other riichi tests: East's first draw, South,
+        // West, North, then East's winning draw. South's ankan draws its rinshan replacement from
+        // the dead wall instead, so the live wall sequence is untouched.
+        let wall = vec![tile("4p"), tile("7m"), tile("8m"), tile("9m"), tile("9s")];
+        let dead_wall = vec![tile("1s")];
+        let state = GameState::new(hands, wall, dead_wall, Vec::new(), Vec::new(), Direction::East, 0, 0);
+
+        let state = state.apply(Direction::East, Action::Draw).unwrap();
+        let state = state.apply(Direction::East, Action::Riichi(tile("9s"))).unwrap();
+        let state = round_of_passes(&state, Direction::East);
+
+        let state = state.apply(Direction::South, Action::Draw).unwrap();
+        let kan = state.hand(Direction::South).closed_kan()[0].clone();
+        let state = state.apply(Direction::South, Action::Kan(kan)).unwrap();
+        let state = state.apply(Direction::South, Action::Discard(tile("9m"))).unwrap();
+        let state = round_of_passes(&state, Direction::South);
+
+        let state = state.apply(Direction::West, Action::Draw).unwrap();
+        let state = state.apply(Direction::West, Action::Discard(tile("8m"))).unwrap();
+        let state = round_of_passes(&state, Direction::West);
+
+        let state = state.apply(Direction::North, Action::Draw).unwrap();
+        let state = state.apply(Direction::North, Action::Discard(tile("7m"))).unwrap();
+        let state = round_of_passes(&state, Direction::North);
+
+        let state = state.apply(Direction::East, Action::Draw).unwrap();
+        let state = state.apply(Direction::East, Action::Tsumo).unwrap();
+
+        let ctx = &state.last_win().unwrap().ctx;
+        assert!(!ctx.first_turn, "South's ankan should have closed the ippatsu window");
+        assert!(ctx.double_riichi, "the ankan came after East's riichi, so it shouldn't undo that");
+    }
+
+    /// `apply` promises to re-validate regardless of what `legal_actions` offered, so a `Kan`
+    /// cannot be smuggled in by shape, tile, or source alone: an ankan is only legal on the
+    /// declarer's own turn, and a daiminkan must match both the tile actually discarded and the
+    /// seat it was discarded by, even when the caller genuinely holds the tiles for some other
+    /// call entirely.
+    #[test]
+    fn apply_kan_action_rejects_a_group_that_doesnt_match_the_real_discard() {
+        let hands = [
+            hand("9999m111p456778s"), // East: four 9m for an ankan, three 1p for a daiminkan.
+            hand("111222333m66p77p"), // South: discards the real 1p East may legitimately kan.
+            hand("111222333m88p99p"),
+            hand("111222333m11z22z"),
+        ];
+        let wall = vec![tile("1p"), tile("9s")];
+        let dead_wall = vec![tile("2s")];
+        let state = GameState::new(hands, wall, dead_wall, Vec::new(), Vec::new(), Direction::East, 0, 0);
+
+        let state = state.apply(Direction::East, Action::Draw).unwrap();
+        let state = state.apply(Direction::East, Action::Discard(tile("9s"))).unwrap();
+        let state = round_of_passes(&state, Direction::East);
+
+        let state = state.apply(Direction::South, Action::Draw).unwrap();
+        let state = state.apply(Direction::South, Action::Discard(tile("1p"))).unwrap();
+
+        // An ankan-shaped group is only legal on the declarer's own turn, never as a response to
+        // another seat's discard.
+        let ankan = state.hand(Direction::East).closed_kan()[0].clone();
+        assert!(state.apply(Direction::East, Action::Kan(ankan)).is_err());
+
+        // A daiminkan-shaped group whose last tile doesn't match the real discard must be
+        // rejected even though its shape is otherwise legal here.
+        let wrong_tile = state
+            .hand(Direction::East)
+            .possible_calls(tile("9m").tile, Opponent::Left)
+            .into_iter()
+            .find(|g| g.ty() == GroupType::Quad)
+            .unwrap();
+        assert!(state.apply(Direction::East, Action::Kan(wrong_tile)).is_err());
+
+        // A daiminkan-shaped group on the right tile but recorded as called off the wrong seat
+        // must also be rejected.
+        let wrong_off = state
+            .hand(Direction::East)
+            .possible_calls(tile("1p").tile, Opponent::Right)
+            .into_iter()
+            .find(|g| g.ty() == GroupType::Quad)
+            .unwrap();
+        assert!(state.apply(Direction::East, Action::Kan(wrong_off)).is_err());
+
+        // A daiminkan matching the real discard's tile and seat succeeds.
+        let daiminkan = state
+            .hand(Direction::East)
+            .possible_calls(tile("1p").tile, Opponent::Left)
+            .into_iter()
+            .find(|g| g.ty() == GroupType::Quad)
+            .unwrap();
+        assert!(state.apply(Direction::East, Action::Kan(daiminkan)).is_ok());
+    }
+
+    /// The same validation applies to a chi/pon: a `Group` the caller genuinely holds the tiles
+    /// for is still rejected if its last tile or recorded source doesn't match the real discard.
+    #[test]
+    fn apply_call_rejects_a_group_that_doesnt_match_the_real_discard() {
+        let hands = [
+            hand("9999m111p456778s"), // East: three 1p for a pon.
+            hand("111222333m66p77p"), // South: discards the real 1p East may legitimately call.
+            hand("111222333m88p99p"),
+            hand("111222333m11z22z"),
+        ];
+        let wall = vec![tile("1p"), tile("9s")];
+        let state = GameState::new(hands, wall, Vec::new(), Vec::new(), Vec::new(), Direction::East, 0, 0);
+
+        let state = state.apply(Direction::East, Action::Draw).unwrap();
+        let state = state.apply(Direction::East, Action::Discard(tile("9s"))).unwrap();
+        let state = round_of_passes(&state, Direction::East);
+
+        let state = state.apply(Direction::South, Action::Draw).unwrap();
+        let state = state.apply(Direction::South, Action::Discard(tile("1p"))).unwrap();
+
+        // A pon whose last tile doesn't match the real discard must be rejected even though East
+        // genuinely holds the tiles for it.
+        let wrong_tile = state
+            .hand(Direction::East)
+            .possible_calls(tile("9m").tile, Opponent::Left)
+            .into_iter()
+            .find(|g| g.ty() == GroupType::Triplet)
+            .unwrap();
+        assert!(state.apply(Direction::East, Action::Pon(wrong_tile)).is_err());
+
+        // A pon on the right tile but recorded as called off the wrong seat must also be
+        // rejected.
+        let wrong_off = state
+            .hand(Direction::East)
+            .possible_calls(tile("1p").tile, Opponent::Right)
+            .into_iter()
+            .find(|g| g.ty() == GroupType::Triplet)
+            .unwrap();
+        assert!(state.apply(Direction::East, Action::Pon(wrong_off)).is_err());
+
+        // A pon matching the real discard's tile and seat succeeds.
+        let pon = state
+            .hand(Direction::East)
+            .possible_calls(tile("1p").tile, Opponent::Left)
+            .into_iter()
+            .find(|g| g.ty() == GroupType::Triplet)
+            .unwrap();
+        assert!(state.apply(Direction::East, Action::Pon(pon)).is_ok());
+    }
+}
@@ -1,7 +1,10 @@
-use crate::tile::{Direction, Tile};
+use crate::tile::{self, Direction, Dragon, Suit, Tile, TileInstance, Val};
 use failure::Error;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
 
-mod yaku;
+pub mod yaku;
 
 /// The seating positions of a player's opponents.
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -53,12 +56,15 @@ pub struct WinContext {
     pub source: Location,
     /// Whether the winner had declared riichi.
     pub riichi: bool,
-    /// Whether the win occurred on the player's first turn after the start of a hand or after a
-    /// riichi. Note that this field is slightly overloaded as a result, however, a player declaring
-    /// riichi necessarily means that they have had a turn, so there is no ambiguity. If a call is
-    /// successfully made by any player after the start of the hand or the riichi, then it is no
-    /// longer considered to be the first turn.
+    /// Whether the win is still within the ippatsu window: the player's own first go-around, or
+    /// the uninterrupted stretch right after declaring riichi. A call by any player closes the
+    /// window early. See [`double_riichi`](WinContext::double_riichi) for whether a riichi in
+    /// particular was declared on the very first discard of the hand.
     pub first_turn: bool,
+    /// Whether the winner's riichi (if any) was declared on the very first discard of the hand,
+    /// before any player had made a call. Kept separate from `first_turn` because that field is
+    /// reopened for the ippatsu window on every riichi, including one declared well into the hand.
+    pub double_riichi: bool,
     /// Whether the live wall is empty. Whenthe location is a drawn tile, this means that the live
     /// wall was empty after the player's draw.
     pub wall_empty: bool,
@@ -68,6 +74,10 @@ pub struct WinContext {
     pub seat: Direction,
     /// The number of honba currently on the table.
     pub honba: u8,
+    /// The dora indicators currently turned up, live wall and (after a kan) dead wall alike.
+    pub dora_indicators: Vec<Tile>,
+    /// The ura dora indicators, turned up only for a hand won with riichi. Empty otherwise.
+    pub ura_indicators: Vec<Tile>,
 }
 
 /// The kinds of group which can be formed.
@@ -96,6 +106,11 @@ pub enum Wait {
 pub struct Group {
     // The tiles of the group.
     tiles: Vec<Tile>,
+    // The sub-multiset of `tiles`'s values that are physically a red five. Empty for a
+    // freshly-built, not yet committed group (see the `hand` module's `closed_group`/
+    // `called_group`/`chi_group` helpers); filled in by `Hand::add_group`/`add_called_group`/
+    // `upgrade_triplet` once the group is actually added to a hand holding real tile instances.
+    reds: Vec<Tile>,
     // The player off of whom a tile was called. If set, the tile called will be last in the group,
     // except possibly for an added tile.
     off: Option<Opponent>,
@@ -111,7 +126,13 @@ pub struct Group {
 impl Group {
     /// Returns `true` if any tiles for this group came from another player.
     pub fn is_open(&self) -> bool {
-        self.off.is_none()
+        self.off.is_some()
+    }
+
+    /// Returns the opponent a tile for this group was called from, or `None` if it was formed
+    /// entirely from the player's own hand.
+    pub fn off(&self) -> Option<Opponent> {
+        self.off
     }
 
     /// Returns `true` if the group contains the winning tile.
@@ -119,6 +140,12 @@ impl Group {
         self.agari
     }
 
+    /// Returns `true` if the group was created by adding a tile to an existing open triplet
+    /// (shouminkan), as opposed to being called or formed all at once.
+    pub fn is_added(&self) -> bool {
+        self.added
+    }
+
     /// Returns the type of the group.
     pub fn ty(&self) -> GroupType {
         if self.tiles.len() == 4 {
@@ -135,6 +162,22 @@ impl Group {
         &*self.tiles
     }
 
+    /// Returns the group's tiles paired with whether each is a red five, for
+    /// [`Hand::instances`] to assemble the full hand a win is scored against.
+    pub fn instances(&self) -> Vec<TileInstance> {
+        let mut remaining = self.reds.clone();
+        self.tiles
+            .iter()
+            .map(|&t| match remaining.iter().position(|&r| r == t) {
+                Some(pos) => {
+                    remaining.remove(pos);
+                    TileInstance { tile: t, red: true }
+                }
+                None => TileInstance::new(t),
+            })
+            .collect()
+    }
+
     /// Returns either a single tile in a triplet/quad, or the first tile in a sequence.
     pub fn first_tile(&self) -> Tile {
         if self.ty() == GroupType::Sequence {
@@ -156,9 +199,9 @@ impl Group {
             let mut sorted = self.tiles.clone();
             sorted.sort();
             let pos = sorted.iter().position(|&t| t == agari).unwrap();
-            if pos == 2 {
+            if pos == 1 {
                 Some(Wait::Kanchan)
-            } else if (pos == 1 && val == 7) || (pos == 3 && val == 3) {
+            } else if (pos == 0 && val == 7) || (pos == 2 && val == 3) {
                 Some(Wait::Penchan)
             } else {
                 Some(Wait::Ryanmen)
@@ -167,14 +210,805 @@ impl Group {
     }
 }
 
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", tile::to_notation(&self.tiles))
+    }
+}
+
 /// A hand of tiles held by a player during the game, including any called groups.
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Hand {
     loose: Vec<Tile>,
+    // The sub-multiset of `loose`'s values that are physically a red five. See [`Group::reds`].
+    loose_reds: Vec<Tile>,
     groups: Vec<Group>,
 }
 
-impl Hand {}
+impl Hand {
+    /// Constructs a closed hand (no called groups) directly from its loose tiles. The tiles are
+    /// taken to be ordinary (non-red) instances; use [`Hand::draw`] to add a red five.
+    pub fn new(loose: Vec<Tile>) -> Hand {
+        Hand { loose, loose_reds: Vec::new(), groups: Vec::new() }
+    }
+
+    /// Returns the hand's loose (uncalled) tiles.
+    pub fn tiles(&self) -> &[Tile] {
+        &self.loose
+    }
+
+    /// Returns the hand's called and closed-kan groups.
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    /// Returns the hand's loose tiles paired with whether each is a red five, in the same order as
+    /// [`tiles`](Hand::tiles).
+    pub fn loose_instances(&self) -> Vec<TileInstance> {
+        let mut remaining = self.loose_reds.clone();
+        self.loose
+            .iter()
+            .map(|&t| match remaining.iter().position(|&r| r == t) {
+                Some(pos) => {
+                    remaining.remove(pos);
+                    TileInstance { tile: t, red: true }
+                }
+                None => TileInstance::new(t),
+            })
+            .collect()
+    }
+
+    /// Returns every tile in the hand, loose and called alike, paired with whether each is a
+    /// physically red five, for [`crate::score::score`]'s dora counting.
+    pub fn instances(&self) -> Vec<TileInstance> {
+        let mut out = self.loose_instances();
+        out.extend(self.groups.iter().flat_map(Group::instances));
+        out
+    }
+
+    /// Enumerates every legal way to arrange this hand's fourteen tiles into a [`CompleteHand`].
+    /// A hand can often be read more than one way (e.g. `111222333` as triplets or as three runs),
+    /// and each reading can score differently, so callers should evaluate yaku and fu against
+    /// every element of the result rather than assuming a single canonical decomposition.
+    pub fn decompositions(&self, ctx: &WinContext) -> Vec<CompleteHand> {
+        let mut out = self.standard_decompositions(ctx.agari);
+        if self.groups.is_empty() {
+            out.extend(self.seven_pairs());
+            out.extend(self.kokushi());
+        }
+        out
+    }
+
+    /// Returns the shanten number of this hand: how many tile exchanges away from tenpai it is.
+    /// `-1` means the hand is already complete. Considers the standard, chiitoitsu, and kokushi
+    /// shapes and returns the best of the three; the latter two only apply to a fully closed hand.
+    pub fn shanten(&self) -> i8 {
+        let mut best = self.standard_shanten();
+        if self.groups.is_empty() {
+            best = best.min(self.chiitoi_shanten());
+            best = best.min(self.kokushi_shanten());
+        }
+        best
+    }
+
+    /// Returns every tile that would complete this hand (shanten -1) if added to it: its waits,
+    /// when the hand is in tenpai.
+    pub fn tenpai_tiles(&self) -> Vec<Tile> {
+        Tile::all()
+            .filter(|&t| {
+                let mut h = self.clone();
+                h.loose.push(t);
+                h.shanten() == -1
+            })
+            .collect()
+    }
+
+    /// Enumerates every chi, pon, and daiminkan this hand could declare on `discard`, each as a
+    /// properly-formed called [`Group`] with `off` set to `from`. Chi is only offered when
+    /// `from == `[`Opponent::Right`] (the kamicha, the only player it may be called from); the
+    /// enumeration only ever looks at tiles within two of `discard` in the same suit, and no call
+    /// is offered once the hand already holds its full four melds, since a fifth would leave it
+    /// unable to reach a legal shape.
+    pub fn possible_calls(&self, discard: Tile, from: Opponent) -> Vec<Group> {
+        if self.groups.len() >= 4 {
+            return Vec::new();
+        }
+        let mut counts = Counts::new();
+        for &t in &self.loose {
+            counts.add(t);
+        }
+        let mut out = Vec::new();
+        if from == Opponent::Right {
+            out.extend(chi_groups(discard, from, &counts));
+        }
+        if counts.get(discard) >= 2 {
+            out.push(called_group(vec![discard, discard, discard], from));
+        }
+        if counts.get(discard) >= 3 {
+            out.push(called_group(vec![discard, discard, discard, discard], from));
+        }
+        out
+    }
+
+    /// Returns every ankan (concealed kan) this hand could declare on its own turn: four copies of
+    /// a tile already among its loose tiles.
+    pub fn closed_kan(&self) -> Vec<Group> {
+        if self.groups.len() >= 4 {
+            return Vec::new();
+        }
+        let mut counts = Counts::new();
+        for &t in &self.loose {
+            counts.add(t);
+        }
+        Tile::all()
+            .filter(|&t| counts.get(t) >= 4)
+            .map(|t| closed_group(vec![t, t, t, t]))
+            .collect()
+    }
+
+    /// Returns every shouminkan (added kan) this hand could declare: a loose tile matching an
+    /// already-called (open) triplet.
+    pub fn added_kan(&self) -> Vec<Group> {
+        self.loose
+            .iter()
+            .filter_map(|&t| {
+                self.groups
+                    .iter()
+                    .find(|g| g.ty() == GroupType::Triplet && g.is_open() && g.first_tile() == t)
+                    .map(|g| {
+                        let mut tiles = g.tiles.clone();
+                        tiles.push(t);
+                        Group {
+                            tiles,
+                            reds: Vec::new(),
+                            off: g.off,
+                            added: true,
+                            agari: false,
+                        }
+                    })
+            })
+            .collect()
+    }
+
+    /// Adds a drawn or claimed tile instance to the hand's loose tiles, for the `game` module to
+    /// apply a draw (or stage a tile being tested for a call or ron before it settles into a
+    /// group).
+    pub(crate) fn draw(&mut self, instance: TileInstance) {
+        self.loose.push(instance.tile);
+        if instance.red {
+            self.loose_reds.push(instance.tile);
+        }
+    }
+
+    /// Removes one instance of each tile in `tiles` from the loose tiles, as an all-or-nothing
+    /// operation, for the `game` module to apply a call or a kan. Returns `false` (leaving the hand
+    /// unchanged) if any tile isn't held in the required quantity. Unlike [`remove_for_discard`],
+    /// this does not drop any red-five marker, since these tiles are moving into a [`Group`]
+    /// rather than leaving the hand; see [`add_group`](Hand::add_group).
+    pub(crate) fn remove_loose(&mut self, tiles: &[Tile]) -> bool {
+        let mut scratch = self.loose.clone();
+        for &t in tiles {
+            match scratch.iter().position(|&x| x == t) {
+                Some(pos) => {
+                    scratch.remove(pos);
+                }
+                None => return false,
+            }
+        }
+        self.loose = scratch;
+        true
+    }
+
+    /// Removes the discarded tile instance from the loose tiles, for the `game` module to apply a
+    /// discard. Returns `false` (leaving the hand unchanged) if the tile isn't held. Unlike
+    /// [`remove_loose`](Hand::remove_loose), this also drops the tile's red-five marker, if any,
+    /// since it is leaving the hand for good.
+    pub(crate) fn remove_for_discard(&mut self, instance: TileInstance) -> bool {
+        if !self.remove_loose(&[instance.tile]) {
+            return false;
+        }
+        if instance.red {
+            if let Some(pos) = self.loose_reds.iter().position(|&r| r == instance.tile) {
+                self.loose_reds.remove(pos);
+            }
+        }
+        true
+    }
+
+    /// Pops the loose red-five marker for each distinct tile value in `tiles` that currently has
+    /// one, returning the values taken. Used when loose tiles already in the hand move into a
+    /// newly-formed [`Group`], so the marker follows them rather than being dropped.
+    fn take_loose_reds(&mut self, tiles: &[Tile]) -> Vec<Tile> {
+        let mut taken = Vec::new();
+        for &t in tiles {
+            if taken.contains(&t) {
+                continue;
+            }
+            if let Some(pos) = self.loose_reds.iter().position(|&r| r == t) {
+                self.loose_reds.remove(pos);
+                taken.push(t);
+            }
+        }
+        taken
+    }
+
+    /// Adds a closed group to the hand, for the `game` module to apply an ankan. Promotes any
+    /// loose red five among the group's own tiles into the group itself.
+    pub(crate) fn add_group(&mut self, mut g: Group) {
+        g.reds = self.take_loose_reds(g.tiles());
+        self.groups.push(g);
+    }
+
+    /// Adds a called group (chi, pon, or daiminkan) to the hand, for the `game` module to apply a
+    /// call. `called` is the claimed tile itself, carrying its own red-five status; the rest of
+    /// the group's tiles were already loose in the hand, so any of their red-five markers are
+    /// promoted the same way as [`add_group`](Hand::add_group).
+    pub(crate) fn add_called_group(&mut self, mut g: Group, called: TileInstance) {
+        let mut own_tiles = g.tiles().to_vec();
+        if let Some(pos) = own_tiles.iter().position(|&t| t == called.tile) {
+            own_tiles.remove(pos);
+        }
+        let mut reds = self.take_loose_reds(&own_tiles);
+        if called.red {
+            reds.push(called.tile);
+        }
+        g.reds = reds;
+        self.groups.push(g);
+    }
+
+    /// Replaces the open triplet matching `kan`'s tile with `kan` itself, for the `game` module to
+    /// apply a shouminkan. Returns `false` (leaving the hand unchanged) if there is no such
+    /// triplet. The triplet's own reds carry over, along with the red-five marker of the tile
+    /// being added to it, if any.
+    pub(crate) fn upgrade_triplet(&mut self, mut kan: Group) -> bool {
+        match self
+            .groups
+            .iter()
+            .position(|g| g.ty() == GroupType::Triplet && g.is_open() && g.first_tile() == kan.first_tile())
+        {
+            Some(pos) => {
+                let added_tile = *kan.tiles().last().expect("a kan has tiles");
+                let mut reds = self.groups[pos].reds.clone();
+                reds.extend(self.take_loose_reds(&[added_tile]));
+                kan.reds = reds;
+                self.groups[pos] = kan;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn standard_shanten(&self) -> i8 {
+        let mut counts = Counts::new();
+        for &t in &self.loose {
+            counts.add(t);
+        }
+        let buckets = [
+            suit_blocks(counts.suits[0]),
+            suit_blocks(counts.suits[1]),
+            suit_blocks(counts.suits[2]),
+            honor_blocks(counts.honors),
+        ];
+        let fixed_m = self.groups.len() as i32;
+        let mut best = i32::MAX;
+        for a in &buckets[0] {
+            for b in &buckets[1] {
+                for c in &buckets[2] {
+                    for d in &buckets[3] {
+                        let m = fixed_m + a.0 as i32 + b.0 as i32 + c.0 as i32 + d.0 as i32;
+                        let has_pair = a.2 || b.2 || c.2 || d.2;
+                        let p = a.1 as i32 + b.1 as i32 + c.1 as i32 + d.1 as i32;
+                        let capped_m = m.min(4);
+                        let mut capped_p = p.min(5 - capped_m);
+                        if capped_m + capped_p == 5 && !has_pair {
+                            capped_p -= 1;
+                        }
+                        let shanten = 8 - 2 * capped_m - capped_p;
+                        best = best.min(shanten);
+                    }
+                }
+            }
+        }
+        best as i8
+    }
+
+    fn chiitoi_shanten(&self) -> i8 {
+        let mut counts = Counts::new();
+        for &t in &self.loose {
+            counts.add(t);
+        }
+        let mut pairs = 0;
+        let mut distinct = 0;
+        for t in Tile::all() {
+            let c = counts.get(t);
+            if c > 0 {
+                distinct += 1;
+            }
+            if c >= 2 {
+                pairs += 1;
+            }
+        }
+        (6 - pairs + (7 - distinct).max(0)) as i8
+    }
+
+    fn kokushi_shanten(&self) -> i8 {
+        let mut distinct = 0;
+        let mut has_pair = false;
+        for t in terminals_and_honors().iter() {
+            let c = self.loose.iter().filter(|&x| x == t).count();
+            if c > 0 {
+                distinct += 1;
+            }
+            if c >= 2 {
+                has_pair = true;
+            }
+        }
+        (13 - distinct - if has_pair { 1 } else { 0 }) as i8
+    }
+
+    fn standard_decompositions(&self, agari: Tile) -> Vec<CompleteHand> {
+        if self.groups.len() > 4 {
+            return Vec::new();
+        }
+        let mut counts = Counts::new();
+        for &t in &self.loose {
+            counts.add(t);
+        }
+        let mut out = Vec::new();
+        for pair_tile in Tile::all().filter(|&t| counts.get(t) >= 2) {
+            let mut remaining = counts;
+            remaining.remove(pair_tile, 2);
+            for melds in decompose_all(remaining) {
+                if self.groups.len() + melds.len() != 4 {
+                    continue;
+                }
+                out.extend(place_agari(&self.groups, melds, [pair_tile, pair_tile], agari));
+            }
+        }
+        out
+    }
+
+    /// Tests whether this (fully closed) hand is a chītoitsu, seven distinct pairs.
+    fn seven_pairs(&self) -> Option<CompleteHand> {
+        if self.loose.len() != 14 {
+            return None;
+        }
+        let mut counts = Counts::new();
+        for &t in &self.loose {
+            counts.add(t);
+        }
+        let mut pairs = Vec::new();
+        for t in Tile::all() {
+            match counts.get(t) {
+                0 => {}
+                2 => pairs.push([t, t]),
+                _ => return None,
+            }
+        }
+        <[[Tile; 2]; 7]>::try_from(pairs)
+            .ok()
+            .map(CompleteHand::SevenPairs)
+    }
+
+    /// Tests whether this (fully closed) hand is a kokushimusō, thirteen orphans.
+    fn kokushi(&self) -> Option<CompleteHand> {
+        if self.loose.len() != 14 {
+            return None;
+        }
+        if !self.loose.iter().all(|t| t.is_terminal() || t.is_honour()) {
+            return None;
+        }
+        let mut counts = Counts::new();
+        for &t in &self.loose {
+            counts.add(t);
+        }
+        let distinct = self
+            .loose
+            .iter()
+            .cloned()
+            .collect::<std::collections::BTreeSet<_>>()
+            .len();
+        if distinct != 13 {
+            return None;
+        }
+        let mut tiles = self.loose.clone();
+        tiles.sort();
+        <[Tile; 14]>::try_from(tiles).ok().map(CompleteHand::Kokushi)
+    }
+}
+
+impl fmt::Display for Hand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", tile::to_notation(&self.loose))?;
+        for g in &self.groups {
+            write!(f, " {}", g)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Hand {
+    type Err = Error;
+
+    /// Parses a closed hand from standard tile notation (e.g. `"111123666789s11z"`). Called
+    /// groups cannot be expressed in this notation, so the result always has none.
+    fn from_str(s: &str) -> Result<Hand, Error> {
+        Ok(Hand::new(tile::parse_tiles(s)?))
+    }
+}
+
+/// A per-suit count of loose tiles, indexed by `val() - 1`, plus a bucket for the seven honour
+/// tiles. Used to drive the recursive divider in [`Hand::decompositions`].
+#[derive(Debug, Clone, Copy)]
+struct Counts {
+    suits: [[u8; 9]; 3],
+    honors: [u8; 7],
+}
+
+const HONOR_TILES: [Tile; 7] = [
+    Tile::Wind(Direction::East),
+    Tile::Wind(Direction::South),
+    Tile::Wind(Direction::West),
+    Tile::Wind(Direction::North),
+    Tile::Dragon(Dragon::White),
+    Tile::Dragon(Dragon::Green),
+    Tile::Dragon(Dragon::Red),
+];
+
+fn suit_index(s: Suit) -> usize {
+    match s {
+        Suit::Manzu => 0,
+        Suit::Souzu => 1,
+        Suit::Pinzu => 2,
+    }
+}
+
+fn honor_index(t: Tile) -> usize {
+    HONOR_TILES.iter().position(|&h| h == t).expect("not an honour tile")
+}
+
+impl Counts {
+    fn new() -> Counts {
+        Counts {
+            suits: [[0; 9]; 3],
+            honors: [0; 7],
+        }
+    }
+
+    fn add(&mut self, t: Tile) {
+        match t {
+            Tile::Suited(s, v) => self.suits[suit_index(s)][v.val() as usize - 1] += 1,
+            _ => self.honors[honor_index(t)] += 1,
+        }
+    }
+
+    fn remove(&mut self, t: Tile, n: u8) {
+        match t {
+            Tile::Suited(s, v) => self.suits[suit_index(s)][v.val() as usize - 1] -= n,
+            _ => self.honors[honor_index(t)] -= n,
+        }
+    }
+
+    fn get(&self, t: Tile) -> u8 {
+        match t {
+            Tile::Suited(s, v) => self.suits[suit_index(s)][v.val() as usize - 1],
+            _ => self.honors[honor_index(t)],
+        }
+    }
+}
+
+/// Constructs a closed (not called, not added) group from a list of tiles.
+fn closed_group(tiles: Vec<Tile>) -> Group {
+    Group {
+        tiles,
+        reds: Vec::new(),
+        off: None,
+        added: false,
+        agari: false,
+    }
+}
+
+/// Constructs a pon or daiminkan group: since every tile is identical, there is no distinction
+/// between the called tile and the rest, so `tiles` can be used as given.
+fn called_group(tiles: Vec<Tile>, from: Opponent) -> Group {
+    Group {
+        tiles,
+        reds: Vec::new(),
+        off: Some(from),
+        added: false,
+        agari: false,
+    }
+}
+
+/// Returns the tile one below `t` in sequence, the inverse of [`Tile::following`]. Only a suited
+/// tile above 1 has a predecessor.
+fn preceding(t: Tile) -> Option<Tile> {
+    match t {
+        Tile::Suited(s, v) if v.val() > 1 => Some(Tile::Suited(s, Val::new(v.val() - 1))),
+        _ => None,
+    }
+}
+
+/// Builds a chi group from `discard` and the two partner tiles `a`/`b`, which are sorted ahead of
+/// the called tile per [`Group`]'s field convention.
+fn chi_group(discard: Tile, a: Tile, b: Tile, from: Opponent) -> Group {
+    let mut partners = vec![a, b];
+    partners.sort();
+    partners.push(discard);
+    Group {
+        tiles: partners,
+        reds: Vec::new(),
+        off: Some(from),
+        added: false,
+        agari: false,
+    }
+}
+
+/// Enumerates every chi this hand could form with `discard`: as the run's low, middle, or high
+/// tile, provided the other two tiles of that run are present in `counts`.
+fn chi_groups(discard: Tile, from: Opponent, counts: &Counts) -> Vec<Group> {
+    let mut out = Vec::new();
+    if let (Some(a), Some(b)) = (discard.following(), discard.following().and_then(Tile::following)) {
+        if counts.get(a) >= 1 && counts.get(b) >= 1 {
+            out.push(chi_group(discard, a, b, from));
+        }
+    }
+    if let (Some(lo), Some(hi)) = (preceding(discard), discard.following()) {
+        if counts.get(lo) >= 1 && counts.get(hi) >= 1 {
+            out.push(chi_group(discard, lo, hi, from));
+        }
+    }
+    if let (Some(lo), Some(lolo)) = (preceding(discard), preceding(discard).and_then(preceding)) {
+        if counts.get(lo) >= 1 && counts.get(lolo) >= 1 {
+            out.push(chi_group(discard, lo, lolo, from));
+        }
+    }
+    out
+}
+
+/// Recursively consumes a suit's tile histogram into triplets and sequences, returning every
+/// complete way to do so. A tile count that cannot be fully consumed yields no decompositions.
+fn decompose_suit(counts: [u8; 9], suit: Suit) -> Vec<Vec<Group>> {
+    let i = match counts.iter().position(|&c| c > 0) {
+        Some(i) => i,
+        None => return vec![Vec::new()],
+    };
+    let mut out = Vec::new();
+    if counts[i] >= 3 {
+        let mut next = counts;
+        next[i] -= 3;
+        let tile = Tile::Suited(suit, Val::new(i as u8 + 1));
+        for mut rest in decompose_suit(next, suit) {
+            rest.insert(0, closed_group(vec![tile, tile, tile]));
+            out.push(rest);
+        }
+    }
+    if i + 2 < 9 && counts[i] >= 1 && counts[i + 1] >= 1 && counts[i + 2] >= 1 {
+        let mut next = counts;
+        next[i] -= 1;
+        next[i + 1] -= 1;
+        next[i + 2] -= 1;
+        let tiles = vec![
+            Tile::Suited(suit, Val::new(i as u8 + 1)),
+            Tile::Suited(suit, Val::new(i as u8 + 2)),
+            Tile::Suited(suit, Val::new(i as u8 + 3)),
+        ];
+        for mut rest in decompose_suit(next, suit) {
+            rest.insert(0, closed_group(tiles.clone()));
+            out.push(rest);
+        }
+    }
+    out
+}
+
+/// Recursively consumes an honour tile histogram into triplets; honours never form sequences or
+/// (at this stage, with the pair already removed) pairs.
+fn decompose_honors(counts: [u8; 7]) -> Vec<Vec<Group>> {
+    let i = match counts.iter().position(|&c| c > 0) {
+        Some(i) => i,
+        None => return vec![Vec::new()],
+    };
+    if counts[i] != 3 {
+        return Vec::new();
+    }
+    let mut next = counts;
+    next[i] = 0;
+    let tile = HONOR_TILES[i];
+    decompose_honors(next)
+        .into_iter()
+        .map(|mut rest| {
+            rest.insert(0, closed_group(vec![tile, tile, tile]));
+            rest
+        })
+        .collect()
+}
+
+/// Combines the three suits and the honour bucket into every possible full set of melds.
+fn decompose_all(counts: Counts) -> Vec<Vec<Group>> {
+    let manzu = decompose_suit(counts.suits[0], Suit::Manzu);
+    let souzu = decompose_suit(counts.suits[1], Suit::Souzu);
+    let pinzu = decompose_suit(counts.suits[2], Suit::Pinzu);
+    let honors = decompose_honors(counts.honors);
+    let mut out = Vec::new();
+    for m in &manzu {
+        for s in &souzu {
+            for p in &pinzu {
+                for h in &honors {
+                    let mut combo = Vec::with_capacity(m.len() + s.len() + p.len() + h.len());
+                    combo.extend(m.iter().cloned());
+                    combo.extend(s.iter().cloned());
+                    combo.extend(p.iter().cloned());
+                    combo.extend(h.iter().cloned());
+                    out.push(combo);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Moves one instance of `agari` to the end of the group's tiles and sets its agari bit, matching
+/// the convention that the winning tile is last (see [`Group`]'s fields).
+fn with_agari(mut group: Group, agari: Tile) -> Group {
+    let pos = group.tiles.iter().position(|&t| t == agari).unwrap();
+    let tile = group.tiles.remove(pos);
+    group.tiles.push(tile);
+    group.agari = true;
+    group
+}
+
+/// Emits one [`CompleteHand::Standard`] per legal placement of the winning tile: once for each
+/// meld that contains it, and once more if it is the pair itself (a tanki wait, which does not
+/// set any group's agari bit). A closed shanpon or tanki win can therefore yield more than one
+/// decomposition here.
+fn place_agari(
+    fixed: &[Group],
+    melds: Vec<Group>,
+    pair: [Tile; 2],
+    agari: Tile,
+) -> Vec<CompleteHand> {
+    let mut out = Vec::new();
+    for i in 0..melds.len() {
+        if melds[i].tiles.contains(&agari) {
+            let mut placed = melds.clone();
+            placed[i] = with_agari(melds[i].clone(), agari);
+            let mut groups = fixed.to_vec();
+            groups.append(&mut placed);
+            if let Ok(arr) = <[Group; 4]>::try_from(groups) {
+                out.push(CompleteHand::Standard(arr, pair));
+            }
+        }
+    }
+    if pair[0] == agari {
+        let mut groups = fixed.to_vec();
+        groups.extend(melds);
+        if let Ok(arr) = <[Group; 4]>::try_from(groups) {
+            out.push(CompleteHand::Standard(arr, pair));
+        }
+    }
+    out
+}
+
+/// Returns the thirteen tile types (terminals and honours) relevant to kokushi musō.
+fn terminals_and_honors() -> [Tile; 13] {
+    [
+        Tile::Suited(Suit::Manzu, Val::new(1)),
+        Tile::Suited(Suit::Manzu, Val::new(9)),
+        Tile::Suited(Suit::Souzu, Val::new(1)),
+        Tile::Suited(Suit::Souzu, Val::new(9)),
+        Tile::Suited(Suit::Pinzu, Val::new(1)),
+        Tile::Suited(Suit::Pinzu, Val::new(9)),
+        Tile::Wind(Direction::East),
+        Tile::Wind(Direction::South),
+        Tile::Wind(Direction::West),
+        Tile::Wind(Direction::North),
+        Tile::Dragon(Dragon::White),
+        Tile::Dragon(Dragon::Green),
+        Tile::Dragon(Dragon::Red),
+    ]
+}
+
+/// Recursively consumes a suit's tile histogram into every reachable combination of complete
+/// melds (`m`) and two-tile partial sets (`p`, pairs and proto-runs), tracking whether any
+/// partial is a pair. Tiles may also be left unconsumed, since shanten only needs the best
+/// reachable block count, not a full decomposition. Returns the Pareto-optimal frontier of
+/// `(m, p, has_pair)` triples reachable from `counts`.
+fn suit_blocks(counts: [u8; 9]) -> Vec<(u8, u8, bool)> {
+    let i = match counts.iter().position(|&c| c > 0) {
+        Some(i) => i,
+        None => return vec![(0, 0, false)],
+    };
+    let mut results = Vec::new();
+    {
+        let mut c = counts;
+        c[i] -= 1;
+        results.extend(suit_blocks(c));
+    }
+    if counts[i] >= 3 {
+        let mut c = counts;
+        c[i] -= 3;
+        results.extend(suit_blocks(c).into_iter().map(|(m, p, hp)| (m + 1, p, hp)));
+    }
+    if i + 2 < 9 && counts[i] >= 1 && counts[i + 1] >= 1 && counts[i + 2] >= 1 {
+        let mut c = counts;
+        c[i] -= 1;
+        c[i + 1] -= 1;
+        c[i + 2] -= 1;
+        results.extend(suit_blocks(c).into_iter().map(|(m, p, hp)| (m + 1, p, hp)));
+    }
+    if counts[i] >= 2 {
+        let mut c = counts;
+        c[i] -= 2;
+        results.extend(suit_blocks(c).into_iter().map(|(m, p, _)| (m, p + 1, true)));
+    }
+    if i + 1 < 9 && counts[i] >= 1 && counts[i + 1] >= 1 {
+        let mut c = counts;
+        c[i] -= 1;
+        c[i + 1] -= 1;
+        results.extend(suit_blocks(c).into_iter().map(|(m, p, hp)| (m, p + 1, hp)));
+    }
+    if i + 2 < 9 && counts[i] >= 1 && counts[i + 2] >= 1 {
+        let mut c = counts;
+        c[i] -= 1;
+        c[i + 2] -= 1;
+        results.extend(suit_blocks(c).into_iter().map(|(m, p, hp)| (m, p + 1, hp)));
+    }
+    prune(results)
+}
+
+/// Like [`suit_blocks`], but for the honour bucket: only triplets and pairs are possible, since
+/// honours never form sequences.
+fn honor_blocks(counts: [u8; 7]) -> Vec<(u8, u8, bool)> {
+    let i = match counts.iter().position(|&c| c > 0) {
+        Some(i) => i,
+        None => return vec![(0, 0, false)],
+    };
+    let mut results = Vec::new();
+    {
+        let mut c = counts;
+        c[i] -= 1;
+        results.extend(honor_blocks(c));
+    }
+    if counts[i] >= 3 {
+        let mut c = counts;
+        c[i] -= 3;
+        results.extend(honor_blocks(c).into_iter().map(|(m, p, hp)| (m + 1, p, hp)));
+    }
+    if counts[i] >= 2 {
+        let mut c = counts;
+        c[i] -= 2;
+        results.extend(honor_blocks(c).into_iter().map(|(m, p, _)| (m, p + 1, true)));
+    }
+    prune(results)
+}
+
+/// Reduces a set of `(m, p, has_pair)` triples to its Pareto-optimal frontier: a triple is
+/// dropped if another dominates it (at least as many melds, at least as many partials, and at
+/// least as good a pair flag).
+fn prune(mut states: Vec<(u8, u8, bool)>) -> Vec<(u8, u8, bool)> {
+    states.sort();
+    states.dedup();
+    let mut out: Vec<(u8, u8, bool)> = Vec::new();
+    'outer: for s in states {
+        let mut i = 0;
+        while i < out.len() {
+            if dominates(out[i], s) {
+                continue 'outer;
+            }
+            if dominates(s, out[i]) {
+                out.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        out.push(s);
+    }
+    out
+}
+
+fn dominates(a: (u8, u8, bool), b: (u8, u8, bool)) -> bool {
+    a != b && a.0 >= b.0 && a.1 >= b.1 && (a.2 || !b.2)
+}
 
 /// A complete hand of fourteen tiles, arranged into one of the shapes that make a winning hand.
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
@@ -186,3 +1020,149 @@ pub enum CompleteHand {
     /// A normal hand of four groups and a pair. At most one group has the agari bit set.
     Standard([Group; 4], [Tile; 2]),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Regression test for a `Tile::all()` bug that skipped pinzu 1-8 entirely: a pinzu pair
+    /// candidate must still be found when completing the hand.
+    #[test]
+    fn standard_decompositions_finds_a_pinzu_pair() {
+        let hand = Hand::new(tile::parse_tiles("111222333m44p567s").unwrap());
+        let ctx_agari = tile::parse_tiles("4p").unwrap()[0];
+        assert_eq!(hand.shanten(), -1);
+        let found = hand
+            .standard_decompositions(ctx_agari)
+            .iter()
+            .any(|h| matches!(h, CompleteHand::Standard(_, pair) if pair[0] == ctx_agari));
+        assert!(found, "expected a decomposition pairing on 4p");
+    }
+
+    /// Regression test for the same bug as it affected `tenpai_tiles`: a pinzu winning tile must
+    /// be offered, not just manzu/souzu/honour ones.
+    #[test]
+    fn tenpai_tiles_includes_pinzu() {
+        let hand = Hand::new(tile::parse_tiles("111222333m44p55s").unwrap());
+        assert_eq!(hand.shanten(), 0);
+        assert!(hand.tenpai_tiles().contains(&tile::parse_tiles("4p").unwrap()[0]));
+        assert!(hand.tenpai_tiles().contains(&tile::parse_tiles("5s").unwrap()[0]));
+    }
+
+    /// Regression test for the same bug as it affected `chiitoi_shanten`: a chiitoitsu hand whose
+    /// pairs fall in pinzu 1-8 must be recognized as complete.
+    #[test]
+    fn chiitoi_shanten_counts_pinzu_pairs() {
+        let hand = Hand::new(tile::parse_tiles("1122m3344p5566s77z").unwrap());
+        assert_eq!(hand.shanten(), -1);
+    }
+
+    /// Regression test for the same bug as it affected `closed_kan`: an ankan of a pinzu 1-8 tile
+    /// must be offered, not just manzu/souzu/honour ones.
+    #[test]
+    fn closed_kan_offers_pinzu() {
+        let hand = Hand::new(tile::parse_tiles("1111222333m4444p").unwrap());
+        let kan_tile = tile::parse_tiles("4p").unwrap()[0];
+        assert!(hand.closed_kan().iter().any(|g| g.first_tile() == kan_tile));
+    }
+
+    /// A chi is only ever offered from the kamicha, and only when the run's two other tiles are
+    /// actually held.
+    #[test]
+    fn possible_calls_offers_chi_only_from_the_kamicha() {
+        let hand = Hand::new(tile::parse_tiles("23m456p77z").unwrap());
+        let discard = tile::parse_tiles("1m").unwrap()[0];
+        let chis = hand.possible_calls(discard, Opponent::Right);
+        assert_eq!(chis.len(), 1);
+        assert_eq!(chis[0].ty(), GroupType::Sequence);
+        assert!(hand.possible_calls(discard, Opponent::Across).is_empty());
+    }
+
+    /// A pon needs only two matching tiles held; a daiminkan additionally needs a third.
+    #[test]
+    fn possible_calls_offers_pon_and_daiminkan_by_held_count() {
+        let discard = tile::parse_tiles("1m").unwrap()[0];
+
+        let two_held = Hand::new(tile::parse_tiles("11m234p456s77z").unwrap());
+        let calls = two_held.possible_calls(discard, Opponent::Across);
+        assert!(calls.iter().any(|g| g.ty() == GroupType::Triplet));
+        assert!(!calls.iter().any(|g| g.ty() == GroupType::Quad));
+
+        let three_held = Hand::new(tile::parse_tiles("111m234p456s77z").unwrap());
+        let calls = three_held.possible_calls(discard, Opponent::Across);
+        assert!(calls.iter().any(|g| g.ty() == GroupType::Triplet));
+        assert!(calls.iter().any(|g| g.ty() == GroupType::Quad));
+    }
+
+    /// `added_kan` upgrades an already-open triplet with a matching loose tile, producing an open,
+    /// added quad rather than a fresh group.
+    #[test]
+    fn added_kan_offers_a_loose_tile_matching_an_open_triplet() {
+        let mut hand = Hand::new(tile::parse_tiles("123p456s77z4m").unwrap());
+        let four_m = tile::parse_tiles("4m").unwrap()[0];
+        let pon = called_group(vec![four_m, four_m, four_m], Opponent::Across);
+        hand.add_called_group(pon, TileInstance::new(four_m));
+
+        let kans = hand.added_kan();
+        assert_eq!(kans.len(), 1);
+        assert!(kans[0].is_added());
+        assert!(kans[0].is_open());
+        assert_eq!(kans[0].ty(), GroupType::Quad);
+    }
+
+    /// Direct test for `Group::is_open`: a pon called off another seat reports open, while a
+    /// same-valued triplet the hand forms on its own stays closed. This predicate drives fu,
+    /// `is_ankou`/sanankou/suuankou, honitsu's open-reduction, and menzen tsumo, so it needs its
+    /// own coverage rather than riding along on an unrelated commit.
+    #[test]
+    fn a_called_pon_reports_open_while_a_self_formed_triplet_stays_closed() {
+        let mut hand = Hand::new(tile::parse_tiles("11p222333m44p55p").unwrap());
+        let one_p = tile::parse_tiles("1p").unwrap()[0];
+        let pon = hand.possible_calls(one_p, Opponent::Across)[0].clone();
+        hand.add_called_group(pon, TileInstance::new(one_p));
+        hand.add_group(closed_group(tile::parse_tiles("222m").unwrap()));
+
+        assert!(hand.groups().iter().find(|g| g.first_tile() == one_p).unwrap().is_open());
+        let manzu = tile::parse_tiles("2m").unwrap()[0];
+        assert!(!hand.groups().iter().find(|g| g.first_tile() == manzu).unwrap().is_open());
+    }
+
+    /// Direct test for `Group::wait`: a sequence's wait shape depends on where the agari tile sits
+    /// within the sorted run and, for the two edge positions, on whether it's a 3 or a 7. A ryanmen
+    /// (two-sided) wait can complete at either end; a kanchan (closed) wait fills a middle gap; a
+    /// penchan (edge) wait is the special case of a ryanmen that can only complete one way because
+    /// the other end would run off the 1-9 range. This drives pinfu and the "bad wait" fu bonus, so
+    /// it needs its own coverage rather than riding along on an unrelated commit.
+    #[test]
+    fn group_wait_classifies_every_sequence_shape() {
+        let ryanmen = with_agari(
+            closed_group(tile::parse_tiles("345m").unwrap()),
+            tile::parse_tiles("5m").unwrap()[0],
+        );
+        assert_eq!(ryanmen.wait(), Some(Wait::Ryanmen));
+
+        let kanchan = with_agari(
+            closed_group(tile::parse_tiles("123m").unwrap()),
+            tile::parse_tiles("2m").unwrap()[0],
+        );
+        assert_eq!(kanchan.wait(), Some(Wait::Kanchan));
+
+        let low_penchan = with_agari(
+            closed_group(tile::parse_tiles("789m").unwrap()),
+            tile::parse_tiles("7m").unwrap()[0],
+        );
+        assert_eq!(low_penchan.wait(), Some(Wait::Penchan));
+
+        let high_penchan = with_agari(
+            closed_group(tile::parse_tiles("123m").unwrap()),
+            tile::parse_tiles("3m").unwrap()[0],
+        );
+        assert_eq!(high_penchan.wait(), Some(Wait::Penchan));
+
+        let shanpon = with_agari(
+            closed_group(tile::parse_tiles("222m").unwrap()),
+            tile::parse_tiles("2m").unwrap()[0],
+        );
+        assert_eq!(shanpon.wait(), Some(Wait::Shanpon));
+    }
+}
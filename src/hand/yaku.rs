@@ -1,7 +1,9 @@
-use crate::hand::{CompleteHand, WinContext};
+use crate::hand::{CompleteHand, Group, GroupType, Location, Wait, WinContext};
+use crate::tile::{Direction, Dragon, Suit, Tile};
 use lazy_static::lazy_static;
 
 /// The value of a yaku.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Val {
     /// The yaku is worth some number of han.
     Han(u8),
@@ -14,6 +16,7 @@ pub enum Val {
 }
 
 /// The value of a yaku in an open hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OpenVal {
     /// The yaku is worth full value.
     Full,
@@ -39,6 +42,231 @@ pub struct Yaku {
     pub in_hand: fn(&CompleteHand, &WinContext) -> bool,
 }
 
+impl Yaku {
+    /// Returns the value of this yaku given whether the hand it was matched against is closed,
+    /// or `None` if it cannot occur at all in that state (an open hand and an
+    /// [`OpenVal::Invalid`] yaku).
+    pub fn value(&self, closed: bool) -> Option<Val> {
+        match (self.open_val, closed) {
+            (OpenVal::Invalid, false) => None,
+            (OpenVal::Reduced, false) => Some(match self.val {
+                Val::Han(h) => Val::Han(h - 1),
+                full => full,
+            }),
+            _ => Some(self.val),
+        }
+    }
+}
+
+/// Returns the groups of a standard hand, or `None` for chiitoitsu/kokushi.
+fn groups(hand: &CompleteHand) -> Option<&[Group; 4]> {
+    match hand {
+        CompleteHand::Standard(groups, _) => Some(groups),
+        _ => None,
+    }
+}
+
+/// Returns the pair of a standard hand, or `None` for chiitoitsu/kokushi.
+fn pair(hand: &CompleteHand) -> Option<[Tile; 2]> {
+    match hand {
+        CompleteHand::Standard(_, pair) => Some(*pair),
+        _ => None,
+    }
+}
+
+/// Returns every tile making up the hand, regardless of its shape.
+fn all_tiles(hand: &CompleteHand) -> Vec<Tile> {
+    match hand {
+        CompleteHand::Kokushi(tiles) => tiles.to_vec(),
+        CompleteHand::SevenPairs(pairs) => pairs.iter().flatten().copied().collect(),
+        CompleteHand::Standard(groups, pair) => {
+            let mut tiles: Vec<Tile> = groups.iter().flat_map(|g| g.tiles().iter().copied()).collect();
+            tiles.extend_from_slice(pair);
+            tiles
+        }
+    }
+}
+
+/// Returns `true` if no group of the hand was called. Chiitoitsu and kokushi are always closed.
+pub(crate) fn is_closed(hand: &CompleteHand) -> bool {
+    groups(hand).is_none_or(|gs| gs.iter().all(|g| !g.is_open()))
+}
+
+/// Returns the `(suit, starting value)` of every sequence meld in the hand.
+fn sequences(hand: &CompleteHand) -> Vec<(Suit, u8)> {
+    groups(hand)
+        .into_iter()
+        .flatten()
+        .filter(|g| g.ty() == GroupType::Sequence)
+        .filter_map(|g| match g.first_tile() {
+            Tile::Suited(s, v) => Some((s, v.val())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the representative tile of every triplet/quad meld in the hand.
+fn triplets(hand: &CompleteHand) -> Vec<Tile> {
+    groups(hand)
+        .into_iter()
+        .flatten()
+        .filter(|g| g.ty() != GroupType::Sequence)
+        .map(Group::first_tile)
+        .collect()
+}
+
+/// Returns `true` if the group is a concealed (ankou) triplet/quad, for yaku like sanankou and for
+/// [`crate::score::count_fu`] alike: it wasn't called, and it wasn't completed by ron (a triplet
+/// finished off a discard counts as open even though the rest of the hand may be concealed).
+pub(crate) fn is_ankou(g: &Group, ctx: &WinContext) -> bool {
+    g.ty() != GroupType::Sequence && !g.is_open() && (!g.has_agari() || ctx.source.is_drawn())
+}
+
+fn concealed_triplet_count(hand: &CompleteHand, ctx: &WinContext) -> usize {
+    groups(hand).map_or(0, |gs| gs.iter().filter(|g| is_ankou(g, ctx)).count())
+}
+
+fn wind_triplet_count(hand: &CompleteHand) -> usize {
+    groups(hand).map_or(0, |gs| {
+        gs.iter()
+            .filter(|g| g.ty() != GroupType::Sequence && matches!(g.first_tile(), Tile::Wind(_)))
+            .count()
+    })
+}
+
+fn wind_yakuhai(hand: &CompleteHand, wind: Direction, ctx: &WinContext) -> bool {
+    groups(hand).is_some_and(|gs| {
+        gs.iter().any(|g| {
+            g.ty() != GroupType::Sequence
+                && g.first_tile() == Tile::Wind(wind)
+                && g.first_tile().is_yakuhai(ctx.round, ctx.seat)
+        })
+    })
+}
+
+fn dragon_yakuhai(hand: &CompleteHand, dragon: Dragon) -> bool {
+    groups(hand).is_some_and(|gs| {
+        gs.iter()
+            .any(|g| g.ty() != GroupType::Sequence && g.first_tile() == Tile::Dragon(dragon))
+    })
+}
+
+fn sanshoku_doujun(hand: &CompleteHand) -> bool {
+    let seqs = sequences(hand);
+    (1..=7).any(|v| {
+        [Suit::Manzu, Suit::Souzu, Suit::Pinzu]
+            .iter()
+            .all(|&s| seqs.contains(&(s, v)))
+    })
+}
+
+fn sanshoku_doukou(hand: &CompleteHand) -> bool {
+    let trips: Vec<(Suit, u8)> = triplets(hand)
+        .into_iter()
+        .filter_map(|t| match t {
+            Tile::Suited(s, v) => Some((s, v.val())),
+            _ => None,
+        })
+        .collect();
+    (1..=9).any(|v| {
+        [Suit::Manzu, Suit::Souzu, Suit::Pinzu]
+            .iter()
+            .all(|&s| trips.contains(&(s, v)))
+    })
+}
+
+fn ittsuu(hand: &CompleteHand) -> bool {
+    let seqs = sequences(hand);
+    [Suit::Manzu, Suit::Souzu, Suit::Pinzu]
+        .iter()
+        .any(|&s| [1, 4, 7].iter().all(|&v| seqs.contains(&(s, v))))
+}
+
+fn tile_counts_as_chanta(t: Tile, honors_ok: bool) -> bool {
+    if honors_ok {
+        t.is_terminal() || t.is_honour()
+    } else {
+        t.is_terminal()
+    }
+}
+
+fn group_counts_as_chanta(g: &Group, honors_ok: bool) -> bool {
+    if g.ty() == GroupType::Sequence {
+        matches!(g.first_tile(), Tile::Suited(_, v) if v.val() == 1 || v.val() == 7)
+    } else {
+        tile_counts_as_chanta(g.first_tile(), honors_ok)
+    }
+}
+
+fn chanta_like(hand: &CompleteHand, honors_ok: bool) -> bool {
+    match hand {
+        CompleteHand::Standard(groups, pair) => {
+            groups.iter().all(|g| group_counts_as_chanta(g, honors_ok))
+                && pair.iter().all(|&t| tile_counts_as_chanta(t, honors_ok))
+        }
+        _ => false,
+    }
+}
+
+fn toitoi(hand: &CompleteHand) -> bool {
+    groups(hand).is_some_and(|gs| gs.iter().all(|g| g.ty() != GroupType::Sequence))
+}
+
+/// Returns `(honitsu, chinitsu)`: whether the hand uses at most one suit (with or without
+/// honours), and whether it uses exactly one suit and no honours at all.
+fn suit_purity(hand: &CompleteHand) -> (bool, bool) {
+    let tiles = all_tiles(hand);
+    let mut suits_seen: Vec<Suit> = Vec::new();
+    let mut any_honor = false;
+    for t in &tiles {
+        match t {
+            Tile::Suited(s, _) => {
+                if !suits_seen.contains(s) {
+                    suits_seen.push(*s);
+                }
+            }
+            _ => any_honor = true,
+        }
+    }
+    let honitsu = suits_seen.len() <= 1;
+    (honitsu, honitsu && !any_honor)
+}
+
+const CHUUREN_BASE: [u8; 9] = [3, 1, 1, 1, 1, 1, 1, 1, 3];
+
+/// Tests for chūren pōtō (nine gates): a closed, single-suit hand with at least three of both
+/// terminals and one of every other value. `pure` additionally requires that the hand without the
+/// winning tile was already exactly that base shape, i.e. the wait was nine-sided.
+fn chuuren_poutou(hand: &CompleteHand, ctx: &WinContext, pure: bool) -> bool {
+    if !is_closed(hand) {
+        return false;
+    }
+    let tiles = all_tiles(hand);
+    let suit = match tiles.first() {
+        Some(Tile::Suited(s, _)) => *s,
+        _ => return false,
+    };
+    let mut counts = [0u8; 9];
+    for t in &tiles {
+        match t {
+            Tile::Suited(s, v) if *s == suit => counts[v.val() as usize - 1] += 1,
+            _ => return false,
+        }
+    }
+    if !counts.iter().zip(CHUUREN_BASE.iter()).all(|(&c, &b)| c >= b) {
+        return false;
+    }
+    if !pure {
+        return true;
+    }
+    let mut without_agari = counts;
+    match ctx.agari {
+        Tile::Suited(s, v) if s == suit => without_agari[v.val() as usize - 1] -= 1,
+        _ => return false,
+    }
+    without_agari == CHUUREN_BASE
+}
+
 lazy_static! {
     static ref KOKUSHI: Yaku = Yaku {
         kanji: "",
@@ -46,10 +274,7 @@ lazy_static! {
         english: "Thirteen Orphans",
         val: Val::Yakuman,
         open_val: OpenVal::Invalid,
-        in_hand: |h, _| match h {
-            CompleteHand::Kokushi(_) => true,
-            _ => false,
-        },
+        in_hand: |h, _| matches!(h, CompleteHand::Kokushi(_)),
     };
     static ref KOKUSHI_13: Yaku = Yaku {
         kanji: "",
@@ -62,4 +287,529 @@ lazy_static! {
             _ => false,
         },
     };
+    static ref RIICHI: Yaku = Yaku {
+        kanji: "立直",
+        romaji: "riichi",
+        english: "Ready Hand",
+        val: Val::Han(1),
+        open_val: OpenVal::Invalid,
+        in_hand: |_, ctx| ctx.riichi,
+    };
+    static ref DOUBLE_RIICHI: Yaku = Yaku {
+        kanji: "ダブル立直",
+        romaji: "daburu riichi",
+        english: "Double Ready Hand",
+        val: Val::Han(1),
+        open_val: OpenVal::Invalid,
+        in_hand: |_, ctx| ctx.riichi && ctx.double_riichi,
+    };
+    static ref IPPATSU: Yaku = Yaku {
+        kanji: "一発",
+        romaji: "ippatsu",
+        english: "One-Shot Win",
+        val: Val::Han(1),
+        open_val: OpenVal::Invalid,
+        in_hand: |_, ctx| ctx.riichi && ctx.first_turn,
+    };
+    static ref MENZEN_TSUMO: Yaku = Yaku {
+        kanji: "門前清自摸和",
+        romaji: "menzen tsumo",
+        english: "Self-Draw",
+        val: Val::Han(1),
+        open_val: OpenVal::Invalid,
+        in_hand: |h, ctx| is_closed(h) && ctx.source.is_drawn(),
+    };
+    static ref PINFU: Yaku = Yaku {
+        kanji: "平和",
+        romaji: "pinfu",
+        english: "All Sequences",
+        val: Val::Han(1),
+        open_val: OpenVal::Invalid,
+        in_hand: |h, ctx| {
+            is_closed(h)
+                && match h {
+                    CompleteHand::Standard(groups, pair) => {
+                        groups.iter().all(|g| g.ty() == GroupType::Sequence)
+                            && !pair[0].is_yakuhai(ctx.round, ctx.seat)
+                            && groups
+                                .iter()
+                                .any(|g| g.has_agari() && g.wait() == Some(Wait::Ryanmen))
+                    }
+                    _ => false,
+                }
+        },
+    };
+    static ref TANYAO: Yaku = Yaku {
+        kanji: "断么九",
+        romaji: "tanyao",
+        english: "All Simples",
+        val: Val::Han(1),
+        open_val: OpenVal::Full,
+        in_hand: |h, _| all_tiles(h).iter().all(|t| !t.is_terminal() && !t.is_honour()),
+    };
+    static ref IIPEIKOU: Yaku = Yaku {
+        kanji: "一盃口",
+        romaji: "iipeikou",
+        english: "Pure Double Sequence",
+        val: Val::Han(1),
+        open_val: OpenVal::Invalid,
+        in_hand: |h, _| {
+            is_closed(h) && {
+                let seqs = sequences(h);
+                let mut seen = Vec::new();
+                seqs.iter().any(|s| seen_insert(&mut seen, *s))
+            }
+        },
+    };
+    static ref ROUND_WIND: Yaku = Yaku {
+        kanji: "場風",
+        romaji: "bakaze",
+        english: "Round Wind",
+        val: Val::Han(1),
+        open_val: OpenVal::Full,
+        in_hand: |h, ctx| wind_yakuhai(h, ctx.round, ctx),
+    };
+    static ref SEAT_WIND: Yaku = Yaku {
+        kanji: "自風",
+        romaji: "jikaze",
+        english: "Seat Wind",
+        val: Val::Han(1),
+        open_val: OpenVal::Full,
+        in_hand: |h, ctx| wind_yakuhai(h, ctx.seat, ctx),
+    };
+    static ref HAKU: Yaku = Yaku {
+        kanji: "白",
+        romaji: "haku",
+        english: "White Dragon",
+        val: Val::Han(1),
+        open_val: OpenVal::Full,
+        in_hand: |h, _| dragon_yakuhai(h, Dragon::White),
+    };
+    static ref HATSU: Yaku = Yaku {
+        kanji: "發",
+        romaji: "hatsu",
+        english: "Green Dragon",
+        val: Val::Han(1),
+        open_val: OpenVal::Full,
+        in_hand: |h, _| dragon_yakuhai(h, Dragon::Green),
+    };
+    static ref CHUN: Yaku = Yaku {
+        kanji: "中",
+        romaji: "chun",
+        english: "Red Dragon",
+        val: Val::Han(1),
+        open_val: OpenVal::Full,
+        in_hand: |h, _| dragon_yakuhai(h, Dragon::Red),
+    };
+    static ref SANSHOKU_DOUJUN: Yaku = Yaku {
+        kanji: "三色同順",
+        romaji: "sanshoku doujun",
+        english: "Three Colour Straight",
+        val: Val::Han(2),
+        open_val: OpenVal::Reduced,
+        in_hand: |h, _| sanshoku_doujun(h),
+    };
+    static ref SANSHOKU_DOUKOU: Yaku = Yaku {
+        kanji: "三色同刻",
+        romaji: "sanshoku doukou",
+        english: "Three Colour Triplets",
+        val: Val::Han(2),
+        open_val: OpenVal::Full,
+        in_hand: |h, _| sanshoku_doukou(h),
+    };
+    static ref ITTSUU: Yaku = Yaku {
+        kanji: "一気通貫",
+        romaji: "ittsuu",
+        english: "Pure Straight",
+        val: Val::Han(2),
+        open_val: OpenVal::Reduced,
+        in_hand: |h, _| ittsuu(h),
+    };
+    static ref CHANTA: Yaku = Yaku {
+        kanji: "混全帯幺九",
+        romaji: "chanta",
+        english: "Half Outside Hand",
+        val: Val::Han(2),
+        open_val: OpenVal::Reduced,
+        in_hand: |h, _| chanta_like(h, true),
+    };
+    static ref JUNCHAN: Yaku = Yaku {
+        kanji: "純全帯幺九",
+        romaji: "junchan",
+        english: "Fully Outside Hand",
+        val: Val::Han(3),
+        open_val: OpenVal::Reduced,
+        in_hand: |h, _| chanta_like(h, false),
+    };
+    static ref TOITOI: Yaku = Yaku {
+        kanji: "対々和",
+        romaji: "toitoi",
+        english: "All Triplets",
+        val: Val::Han(2),
+        open_val: OpenVal::Full,
+        in_hand: |h, _| toitoi(h),
+    };
+    static ref SANANKOU: Yaku = Yaku {
+        kanji: "三暗刻",
+        romaji: "sanankou",
+        english: "Three Concealed Triplets",
+        val: Val::Han(2),
+        open_val: OpenVal::Full,
+        in_hand: |h, ctx| concealed_triplet_count(h, ctx) == 3,
+    };
+    static ref HONITSU: Yaku = Yaku {
+        kanji: "混一色",
+        romaji: "honitsu",
+        english: "Half Flush",
+        val: Val::Han(3),
+        open_val: OpenVal::Reduced,
+        in_hand: |h, _| {
+            let (honitsu, chinitsu) = suit_purity(h);
+            honitsu && !chinitsu
+        },
+    };
+    static ref CHINITSU: Yaku = Yaku {
+        kanji: "清一色",
+        romaji: "chinitsu",
+        english: "Full Flush",
+        val: Val::Han(6),
+        open_val: OpenVal::Reduced,
+        in_hand: |h, _| suit_purity(h).1,
+    };
+    static ref CHIITOITSU: Yaku = Yaku {
+        kanji: "七対子",
+        romaji: "chiitoitsu",
+        english: "Seven Pairs",
+        val: Val::Han(2),
+        open_val: OpenVal::Invalid,
+        in_hand: |h, _| matches!(h, CompleteHand::SevenPairs(_)),
+    };
+    static ref RYUUIISOU: Yaku = Yaku {
+        kanji: "緑一色",
+        romaji: "ryūiisō",
+        english: "All Green",
+        val: Val::Yakuman,
+        open_val: OpenVal::Full,
+        in_hand: |h, _| all_tiles(h).iter().all(|t| t.is_green()),
+    };
+    static ref DAISANGEN: Yaku = Yaku {
+        kanji: "大三元",
+        romaji: "daisangen",
+        english: "Big Three Dragons",
+        val: Val::Yakuman,
+        open_val: OpenVal::Full,
+        in_hand: |h, _| {
+            groups(h).is_some_and(|gs| {
+                gs.iter()
+                    .filter(|g| g.ty() != GroupType::Sequence && matches!(g.first_tile(), Tile::Dragon(_)))
+                    .count()
+                    == 3
+            })
+        },
+    };
+    static ref SUUANKOU: Yaku = Yaku {
+        kanji: "四暗刻",
+        romaji: "suuankou",
+        english: "Four Concealed Triplets",
+        val: Val::Yakuman,
+        open_val: OpenVal::Full,
+        in_hand: |h, ctx| concealed_triplet_count(h, ctx) == 4,
+    };
+    static ref TSUUIISOU: Yaku = Yaku {
+        kanji: "字一色",
+        romaji: "tsūiisō",
+        english: "All Honours",
+        val: Val::Yakuman,
+        open_val: OpenVal::Full,
+        in_hand: |h, _| all_tiles(h).iter().all(|t| t.is_honour()),
+    };
+    static ref CHINROUTOU: Yaku = Yaku {
+        kanji: "清老頭",
+        romaji: "chinroutou",
+        english: "All Terminals",
+        val: Val::Yakuman,
+        open_val: OpenVal::Full,
+        in_hand: |h, _| all_tiles(h).iter().all(|t| t.is_terminal()),
+    };
+    static ref SHOUSUUSHII: Yaku = Yaku {
+        kanji: "小四喜",
+        romaji: "shousuushii",
+        english: "Little Four Winds",
+        val: Val::Yakuman,
+        open_val: OpenVal::Full,
+        in_hand: |h, _| {
+            wind_triplet_count(h) == 3 && matches!(pair(h), Some([Tile::Wind(_), _]))
+        },
+    };
+    static ref DAISUUSHII: Yaku = Yaku {
+        kanji: "大四喜",
+        romaji: "daisuushii",
+        english: "Big Four Winds",
+        val: Val::DoubleYakuman,
+        open_val: OpenVal::Full,
+        in_hand: |h, _| wind_triplet_count(h) == 4,
+    };
+    static ref CHUUREN_POUTOU: Yaku = Yaku {
+        kanji: "九蓮宝燈",
+        romaji: "chūren pōtō",
+        english: "Nine Gates",
+        val: Val::Yakuman,
+        open_val: OpenVal::Invalid,
+        in_hand: |h, ctx| chuuren_poutou(h, ctx, false),
+    };
+    static ref JUNSEI_CHUUREN_POUTOU: Yaku = Yaku {
+        kanji: "純正九蓮宝燈",
+        romaji: "junsei chūren pōtō",
+        english: "True Nine Gates",
+        val: Val::DoubleYakuman,
+        open_val: OpenVal::Invalid,
+        in_hand: |h, ctx| chuuren_poutou(h, ctx, true),
+    };
+    static ref HAITEI: Yaku = Yaku {
+        kanji: "海底摸月",
+        romaji: "haitei raoyue",
+        english: "Last Tile Draw",
+        val: Val::Han(1),
+        open_val: OpenVal::Full,
+        in_hand: |_, ctx| ctx.wall_empty && ctx.source == Location::LiveWall,
+    };
+    static ref HOUTEI: Yaku = Yaku {
+        kanji: "河底撈魚",
+        romaji: "houtei raoyui",
+        english: "Last Discard Win",
+        val: Val::Han(1),
+        open_val: OpenVal::Full,
+        in_hand: |_, ctx| ctx.wall_empty && matches!(ctx.source, Location::Discard(_)),
+    };
+    static ref RINSHAN: Yaku = Yaku {
+        kanji: "嶺上開花",
+        romaji: "rinshan kaihou",
+        english: "Replacement Tile Win",
+        val: Val::Han(1),
+        open_val: OpenVal::Full,
+        in_hand: |_, ctx| ctx.source == Location::DeadWall,
+    };
+    static ref CHANKAN: Yaku = Yaku {
+        kanji: "槍槓",
+        romaji: "chankan",
+        english: "Robbing a Kan",
+        val: Val::Han(1),
+        open_val: OpenVal::Full,
+        in_hand: |_, ctx| matches!(ctx.source, Location::Kan(_)),
+    };
+}
+
+/// Records `s` in `seen`, returning `true` if it was already there. A linear scan is plenty for
+/// the handful of sequences in a hand.
+fn seen_insert(seen: &mut Vec<(Suit, u8)>, s: (Suit, u8)) -> bool {
+    if seen.contains(&s) {
+        true
+    } else {
+        seen.push(s);
+        false
+    }
+}
+
+/// Returns every yaku this crate knows how to detect.
+pub fn all_yaku() -> &'static [&'static Yaku] {
+    lazy_static! {
+        static ref ALL: Vec<&'static Yaku> = vec![
+            &*KOKUSHI,
+            &*KOKUSHI_13,
+            &*RIICHI,
+            &*DOUBLE_RIICHI,
+            &*IPPATSU,
+            &*MENZEN_TSUMO,
+            &*PINFU,
+            &*TANYAO,
+            &*IIPEIKOU,
+            &*ROUND_WIND,
+            &*SEAT_WIND,
+            &*HAKU,
+            &*HATSU,
+            &*CHUN,
+            &*SANSHOKU_DOUJUN,
+            &*SANSHOKU_DOUKOU,
+            &*ITTSUU,
+            &*CHANTA,
+            &*JUNCHAN,
+            &*TOITOI,
+            &*SANANKOU,
+            &*HONITSU,
+            &*CHINITSU,
+            &*CHIITOITSU,
+            &*RYUUIISOU,
+            &*DAISANGEN,
+            &*SUUANKOU,
+            &*TSUUIISOU,
+            &*CHINROUTOU,
+            &*SHOUSUUSHII,
+            &*DAISUUSHII,
+            &*CHUUREN_POUTOU,
+            &*JUNSEI_CHUUREN_POUTOU,
+            &*HAITEI,
+            &*HOUTEI,
+            &*RINSHAN,
+            &*CHANKAN,
+        ];
+    }
+    &ALL
+}
+
+/// Returns every yaku present in a hand, honouring [`OpenVal::Invalid`] for open hands.
+pub fn detect(hand: &CompleteHand, ctx: &WinContext) -> Vec<&'static Yaku> {
+    let closed = is_closed(hand);
+    all_yaku()
+        .iter()
+        .filter(|y| y.open_val != OpenVal::Invalid || closed)
+        .filter(|y| (y.in_hand)(hand, ctx))
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hand::Hand;
+    use crate::tile;
+
+    /// A single complete decomposition of `notation` (fourteen tiles), won on `agari`.
+    fn complete_hand(notation: &str, agari: &str) -> CompleteHand {
+        let hand = Hand::new(tile::parse_tiles(notation).unwrap());
+        let agari = tile::parse_tiles(agari).unwrap()[0];
+        hand.decompositions(&base_ctx(agari, Location::LiveWall))
+            .into_iter()
+            .next()
+            .expect("expected at least one decomposition")
+    }
+
+    fn base_ctx(agari: Tile, source: Location) -> WinContext {
+        WinContext {
+            agari,
+            source,
+            riichi: false,
+            first_turn: false,
+            double_riichi: false,
+            wall_empty: false,
+            round: Direction::East,
+            seat: Direction::East,
+            honba: 0,
+            dora_indicators: Vec::new(),
+            ura_indicators: Vec::new(),
+        }
+    }
+
+    /// Once the live wall has run out, a later rinshan tsumo (a kan replacement draw) must not also
+    /// count as haitei: the two are mutually exclusive even though `Location::is_drawn` is true for
+    /// both a live-wall and a dead-wall draw.
+    #[test]
+    fn haitei_and_rinshan_are_mutually_exclusive_on_an_empty_wall() {
+        let hand = complete_hand("111222333m44p567s", "4p");
+        let agari = tile::parse_tiles("4p").unwrap()[0];
+
+        let mut ctx = base_ctx(agari, Location::LiveWall);
+        ctx.wall_empty = true;
+        assert!((HAITEI.in_hand)(&hand, &ctx));
+        assert!(!(RINSHAN.in_hand)(&hand, &ctx));
+
+        let mut ctx = base_ctx(agari, Location::DeadWall);
+        ctx.wall_empty = true;
+        assert!(!(HAITEI.in_hand)(&hand, &ctx));
+        assert!((RINSHAN.in_hand)(&hand, &ctx));
+    }
+
+    /// Tanyao requires every tile in the hand, groups and pair alike, to be a simple (2-8):
+    /// a single terminal anywhere in an otherwise all-simples hand disqualifies it.
+    #[test]
+    fn tanyao_rejects_any_terminal_or_honour_tile() {
+        let agari = tile::parse_tiles("5s").unwrap()[0];
+        let ctx = base_ctx(agari, Location::LiveWall);
+
+        let simple_hand = complete_hand("234456678m234p55s", "5s");
+        assert!((TANYAO.in_hand)(&simple_hand, &ctx));
+
+        let terminal_hand = complete_hand("123456678m234p55s", "5s");
+        assert!(!(TANYAO.in_hand)(&terminal_hand, &ctx));
+    }
+
+    /// Bakaze and jikaze only fire for a triplet of the matching wind: the same hand qualifies for
+    /// both when the round and seat wind coincide, and for neither once they're changed to a wind
+    /// the hand doesn't hold a triplet of.
+    #[test]
+    fn wind_yakuhai_matches_only_the_round_or_seat_wind() {
+        let hand = complete_hand("111z222333m444p55s", "5s");
+        let agari = tile::parse_tiles("5s").unwrap()[0];
+        let mut ctx = base_ctx(agari, Location::LiveWall);
+        ctx.round = Direction::East;
+        ctx.seat = Direction::East;
+        assert!((ROUND_WIND.in_hand)(&hand, &ctx));
+        assert!((SEAT_WIND.in_hand)(&hand, &ctx));
+
+        ctx.round = Direction::South;
+        ctx.seat = Direction::South;
+        assert!(!(ROUND_WIND.in_hand)(&hand, &ctx));
+        assert!(!(SEAT_WIND.in_hand)(&hand, &ctx));
+    }
+
+    /// Toitoi and honitsu are independent shape checks that can both hold of the same hand: every
+    /// group here is a triplet (toitoi), and the only suit present alongside honour tiles is manzu
+    /// (honitsu, not the stricter honours-free chinitsu).
+    #[test]
+    fn toitoi_and_honitsu_recognize_an_all_triplet_single_suit_hand() {
+        let hand = complete_hand("111444777m111z22z", "2z");
+        let agari = tile::parse_tiles("2z").unwrap()[0];
+        let ctx = base_ctx(agari, Location::LiveWall);
+        assert!((TOITOI.in_hand)(&hand, &ctx));
+        assert!((HONITSU.in_hand)(&hand, &ctx));
+        assert!(!(CHINITSU.in_hand)(&hand, &ctx));
+    }
+
+    /// Chinitsu additionally requires there be no honour tiles at all, unlike honitsu.
+    #[test]
+    fn chinitsu_requires_a_single_suit_with_no_honours() {
+        let hand = complete_hand("111222333444m55m", "5m");
+        let agari = tile::parse_tiles("5m").unwrap()[0];
+        let ctx = base_ctx(agari, Location::LiveWall);
+        assert!((CHINITSU.in_hand)(&hand, &ctx));
+        assert!(!(HONITSU.in_hand)(&hand, &ctx));
+    }
+
+    /// Iipeikou requires two *identical* sequences in the same suit, not merely the presence of
+    /// any sequence at all: a hand with two copies of 123m qualifies, but a hand whose sequences
+    /// are all distinct (here three separate manzu runs) must not.
+    #[test]
+    fn iipeikou_requires_a_duplicated_sequence_not_just_any_sequence() {
+        let agari = tile::parse_tiles("5z").unwrap()[0];
+        let ctx = base_ctx(agari, Location::LiveWall);
+
+        let duplicated = complete_hand("112233m456p789s55z", "5z");
+        assert!((IIPEIKOU.in_hand)(&duplicated, &ctx));
+
+        let all_distinct = complete_hand("123456789m22p345s", "5s");
+        let agari = tile::parse_tiles("5s").unwrap()[0];
+        let ctx = base_ctx(agari, Location::LiveWall);
+        assert!(!(IIPEIKOU.in_hand)(&all_distinct, &ctx));
+    }
+
+    /// Pinfu requires a genuine ryanmen (two-sided) wait on the agari group: the same all-sequence,
+    /// non-yakuhai-pair shape must be disqualified when the winning tile instead completed a
+    /// kanchan or penchan wait.
+    #[test]
+    fn pinfu_requires_a_genuine_ryanmen_wait() {
+        let ryanmen = complete_hand("345m456p789s456s22z", "5m");
+        let agari = tile::parse_tiles("5m").unwrap()[0];
+        let ctx = base_ctx(agari, Location::LiveWall);
+        assert!((PINFU.in_hand)(&ryanmen, &ctx));
+
+        let kanchan = complete_hand("123m456p789s456s22z", "2m");
+        let agari = tile::parse_tiles("2m").unwrap()[0];
+        let ctx = base_ctx(agari, Location::LiveWall);
+        assert!(!(PINFU.in_hand)(&kanchan, &ctx));
+
+        let penchan = complete_hand("123m456p789s456s22z", "3m");
+        let agari = tile::parse_tiles("3m").unwrap()[0];
+        let ctx = base_ctx(agari, Location::LiveWall);
+        assert!(!(PINFU.in_hand)(&penchan, &ctx));
+    }
 }
+
@@ -0,0 +1,7 @@
+//! `mjkit` is a toolkit for building riichi mahjong tools: hand analysis, scoring, and eventually
+//! full game simulation.
+
+pub mod game;
+pub mod hand;
+pub mod score;
+pub mod tile;
@@ -0,0 +1,367 @@
+//! Turns a [`CompleteHand`] and its [`WinContext`] into a fu/han count and a point total, under a
+//! configurable [`Rules`].
+
+use crate::hand::yaku::{self, Val, Yaku};
+use crate::hand::{CompleteHand, Group, GroupType, Wait, WinContext};
+use crate::tile::{self, Direction, TileInstance};
+
+use yaku::is_closed;
+
+/// Rule variations that affect scoring but not yaku detection.
+#[derive(Debug, Clone, Copy)]
+pub struct Rules {
+    /// Whether a hand worth 13 or more han purely through ordinary yaku and dora (as opposed to
+    /// an actual yakuman) is capped at the kazoe yakuman tier, rather than continuing to double
+    /// without limit.
+    pub kazoe_yakuman: bool,
+    /// Whether a score that narrowly misses mangan (han 4 fu 30, or han 3 fu 60) is rounded up
+    /// to a full mangan instead of being paid at its exact value.
+    pub kiriage_mangan: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            kazoe_yakuman: true,
+            kiriage_mangan: false,
+        }
+    }
+}
+
+/// The points a win collects from the other players, before anyone's starting stake is considered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Payments {
+    /// A single payment from the player who dealt into the win.
+    Ron(u32),
+    /// Payments collected from the other three players on a self-draw. `from_dealer` is what the
+    /// dealer pays (or, if the winner is the dealer, what each of the other three players pays);
+    /// `from_non_dealer` is what a non-dealer pays (unused when the winner is the dealer).
+    Tsumo { from_dealer: u32, from_non_dealer: u32 },
+}
+
+/// Sums the han value of every detected yaku, collapsing yakuman tiers into their conventional
+/// 13/26-han equivalents so the result can feed straight into [`payments`].
+pub fn yaku_han(detected: &[&'static Yaku], closed: bool) -> u8 {
+    let mut total: u32 = 0;
+    for y in detected {
+        total += match y.value(closed) {
+            Some(Val::Han(h)) => h as u32,
+            Some(Val::Mangan) => 5,
+            Some(Val::Yakuman) => 13,
+            Some(Val::DoubleYakuman) => 26,
+            None => 0,
+        };
+    }
+    total.min(u32::from(u8::MAX)) as u8
+}
+
+/// Returns `true` if any detected yaku is a genuine yakuman or double yakuman, as opposed to the
+/// same han total being reached purely by stacking ordinary yaku and dora. [`base_points`] uses
+/// this to tell a true double yakuman apart from a merely kazoe (counted) one.
+fn has_genuine_yakuman(detected: &[&'static Yaku], closed: bool) -> bool {
+    detected
+        .iter()
+        .any(|y| matches!(y.value(closed), Some(Val::Yakuman) | Some(Val::DoubleYakuman)))
+}
+
+/// Returns the fu value of a group: 0 for a sequence, otherwise the simple/terminal-honour
+/// triplet value scaled for a quad and doubled if the group is concealed. A group finished off a
+/// ron counts as open (minko) for this purpose even when the rest of the hand is concealed; see
+/// [`yaku::is_ankou`].
+fn group_fu(g: &Group, ctx: &WinContext) -> u32 {
+    if g.ty() == GroupType::Sequence {
+        return 0;
+    }
+    let base = if g.first_tile().is_terminal() || g.first_tile().is_honour() {
+        4
+    } else {
+        2
+    };
+    let base = if g.ty() == GroupType::Quad { base * 4 } else { base };
+    if yaku::is_ankou(g, ctx) {
+        base * 2
+    } else {
+        base
+    }
+}
+
+/// Returns the fu (scoring points) of a winning hand, per the usual fu table: a base of 20, plus
+/// bonuses for a closed ron, a self-draw, a bad wait, a yakuhai pair, and each group's own value.
+/// Chiitoitsu is a fixed 25 fu; kokushi has no fu-based scoring at all. The result is rounded up
+/// to the next multiple of 10, except for the fixed-value shapes above and for pinfu, which is
+/// always exactly 20 fu on a self-draw or 30 fu on a ron.
+pub fn count_fu(hand: &CompleteHand, ctx: &WinContext, closed: bool) -> u8 {
+    let (groups, pair) = match hand {
+        CompleteHand::Standard(groups, pair) => (groups, pair),
+        CompleteHand::SevenPairs(_) => return 25,
+        CompleteHand::Kokushi(_) => return 20,
+    };
+    let ron = !ctx.source.is_drawn();
+    let agari_group = groups.iter().find(|g| g.has_agari());
+    let is_pinfu = closed
+        && groups.iter().all(|g| g.ty() == GroupType::Sequence)
+        && !pair[0].is_yakuhai(ctx.round, ctx.seat)
+        && agari_group.is_some_and(|g| g.wait() == Some(Wait::Ryanmen));
+    if is_pinfu {
+        return if ron { 30 } else { 20 };
+    }
+
+    let mut fu: u32 = 20;
+    if closed && ron {
+        fu += 10;
+    }
+    if !ron {
+        fu += 2;
+    }
+    let bad_wait = match agari_group {
+        None => true, // no group holds the agari bit: the pair itself completed a tanki wait
+        Some(g) => matches!(g.wait(), Some(Wait::Kanchan) | Some(Wait::Penchan)),
+    };
+    if bad_wait {
+        fu += 2;
+    }
+    if pair[0].is_yakuhai(ctx.round, ctx.seat) {
+        fu += 2;
+    }
+    fu += groups.iter().map(|g| group_fu(g, ctx)).sum::<u32>();
+    fu.div_ceil(10) as u8 * 10
+}
+
+/// Rounds a point total up to the next multiple of 100, as every payment in the game is made in
+/// units of 100 points.
+fn round_up_100(points: u32) -> u32 {
+    points.div_ceil(100) * 100
+}
+
+/// Returns the base point value for a han/fu combination, applying the mangan-and-above tiers.
+/// `has_yakuman` distinguishes a hand that reached 13+ han through an actual yakuman/double
+/// yakuman yaku (which doubles normally) from one that only got there by stacking ordinary yaku
+/// and dora (which, under `kazoe_yakuman`, is capped at a single yakuman's worth of points).
+fn base_points(han: u8, fu: u8, rules: &Rules, has_yakuman: bool) -> u32 {
+    match han {
+        0..=4 => {
+            let raw = u32::from(fu) * 2u32.pow(2 + u32::from(han));
+            if rules.kiriage_mangan && raw >= 1920 {
+                2000
+            } else {
+                raw.min(2000)
+            }
+        }
+        5 => 2000,
+        6 | 7 => 3000,
+        8..=10 => 4000,
+        11 | 12 => 6000,
+        _ => {
+            if has_yakuman {
+                8000 * u32::from(han / 13)
+            } else if rules.kazoe_yakuman {
+                8000
+            } else {
+                u32::from(fu) * 2u32.pow(2 + u32::from(han))
+            }
+        }
+    }
+}
+
+/// Computes what each other player pays for a win worth `han` han and `fu` fu, including any
+/// honba bonus, but not including riichi sticks left on the table. `has_yakuman` should be `true`
+/// if a genuine yakuman/double yakuman yaku (as opposed to ordinary yaku and dora stacking alone)
+/// was detected; see [`base_points`].
+pub fn payments(han: u8, fu: u8, ctx: &WinContext, rules: &Rules, has_yakuman: bool) -> Payments {
+    let base = base_points(han, fu, rules, has_yakuman);
+    let honba = u32::from(ctx.honba) * 100;
+    let dealer = ctx.seat == Direction::East;
+    if ctx.source.is_drawn() {
+        let from_dealer = round_up_100(base * 2) + honba;
+        let from_non_dealer = round_up_100(if dealer { base * 2 } else { base }) + honba;
+        Payments::Tsumo { from_dealer, from_non_dealer }
+    } else {
+        let multiplier = if dealer { 6 } else { 4 };
+        Payments::Ron(round_up_100(base * multiplier) + honba * 3)
+    }
+}
+
+/// Detects a hand's yaku and turns them into a han/fu count and a point total in one step.
+/// `instances` are the same fourteen tiles as `hand`, carrying the red-five information that
+/// `CompleteHand` itself discards, and is used together with `ctx`'s dora/ura indicators to add
+/// dora han. Returns `None` if the hand has no yaku, since dora never counts on its own and such
+/// a hand cannot legally win anyway.
+pub fn score(
+    hand: &CompleteHand,
+    instances: &[TileInstance],
+    ctx: &WinContext,
+    rules: &Rules,
+) -> Option<(u8, u8, Payments)> {
+    let closed = is_closed(hand);
+    let detected = yaku::detect(hand, ctx);
+    if detected.is_empty() {
+        return None;
+    }
+    let mut han = u32::from(yaku_han(&detected, closed));
+    han += tile::count_dora(instances, &ctx.dora_indicators);
+    han += tile::dora_matches(instances, &ctx.ura_indicators);
+    let han = han.min(u32::from(u8::MAX)) as u8;
+    let fu = count_fu(hand, ctx, closed);
+    let has_yakuman = has_genuine_yakuman(&detected, closed);
+    Some((han, fu, payments(han, fu, ctx, rules, has_yakuman)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hand::{Hand, Location, Opponent};
+    use crate::tile::Tile;
+
+    fn ctx(agari: Tile, source: Location) -> WinContext {
+        WinContext {
+            agari,
+            source,
+            riichi: false,
+            first_turn: false,
+            double_riichi: false,
+            wall_empty: false,
+            round: Direction::East,
+            seat: Direction::East,
+            honba: 0,
+            dora_indicators: Vec::new(),
+            ura_indicators: Vec::new(),
+        }
+    }
+
+    /// A closed triplet finished off by the winning tile via ron counts as open (minko) for fu
+    /// purposes, same as [`yaku::is_ankou`] already treats it for sanankou: it must not be charged
+    /// the doubled concealed rate just because the rest of the hand happens to be closed.
+    #[test]
+    fn a_ron_completed_triplet_is_charged_the_open_fu_rate() {
+        let hand = Hand::new(crate::tile::parse_tiles("123456789m111z55z").unwrap());
+        let agari = crate::tile::parse_tiles("1z").unwrap()[0];
+        let ron_ctx = ctx(agari, Location::Discard(Opponent::Across));
+        let complete = hand
+            .decompositions(&ron_ctx)
+            .into_iter()
+            .find(|h| {
+                matches!(h, CompleteHand::Standard(groups, _)
+                    if groups.iter().any(|g| g.ty() == GroupType::Triplet && g.has_agari()))
+            })
+            .expect("expected a decomposition with a triplet agari group");
+        let CompleteHand::Standard(groups, _) = &complete else {
+            unreachable!()
+        };
+        let agari_group = groups.iter().find(|g| g.has_agari()).unwrap();
+        assert!(!yaku::is_ankou(agari_group, &ron_ctx));
+        assert_eq!(group_fu(agari_group, &ron_ctx), 4);
+
+        // The same triplet won by tsumo instead is a genuine ankou, charged double.
+        let tsumo_ctx = ctx(agari, Location::LiveWall);
+        let complete = hand
+            .decompositions(&tsumo_ctx)
+            .into_iter()
+            .find(|h| {
+                matches!(h, CompleteHand::Standard(groups, _)
+                    if groups.iter().any(|g| g.ty() == GroupType::Triplet && g.has_agari()))
+            })
+            .expect("expected a decomposition with a triplet agari group");
+        let CompleteHand::Standard(groups, _) = &complete else {
+            unreachable!()
+        };
+        let agari_group = groups.iter().find(|g| g.has_agari()).unwrap();
+        assert!(yaku::is_ankou(agari_group, &tsumo_ctx));
+        assert_eq!(group_fu(agari_group, &tsumo_ctx), 8);
+    }
+
+    /// A ryanmen win on this all-sequence, non-yakuhai-pair shape is pinfu, a flat 30 fu on a closed
+    /// ron with no "bad wait" fu; the same shape won on a kanchan or penchan wait loses pinfu and is
+    /// charged the bad-wait fu: 20 base + 10 closed ron + 2, rounded up to 40.
+    #[test]
+    fn count_fu_charges_the_bad_wait_bonus_only_for_kanchan_and_penchan() {
+        let mut c = ctx(crate::tile::parse_tiles("5m").unwrap()[0], Location::Discard(Opponent::Across));
+        c.riichi = true;
+
+        let ryanmen_hand = Hand::new(crate::tile::parse_tiles("345m456p789s456s22z").unwrap());
+        let ryanmen = ryanmen_hand
+            .decompositions(&c)
+            .into_iter()
+            .find(|h| {
+                matches!(h, CompleteHand::Standard(groups, _)
+                    if groups.iter().any(|g| g.has_agari() && g.wait() == Some(Wait::Ryanmen)))
+            })
+            .expect("expected a decomposition with a ryanmen agari group");
+        assert_eq!(count_fu(&ryanmen, &c, true), 30);
+
+        let kanchan_hand = Hand::new(crate::tile::parse_tiles("123m456p789s456s22z").unwrap());
+        c.agari = crate::tile::parse_tiles("2m").unwrap()[0];
+        let kanchan = kanchan_hand
+            .decompositions(&c)
+            .into_iter()
+            .find(|h| {
+                matches!(h, CompleteHand::Standard(groups, _)
+                    if groups.iter().any(|g| g.has_agari() && g.wait() == Some(Wait::Kanchan)))
+            })
+            .expect("expected a decomposition with a kanchan agari group");
+        assert_eq!(count_fu(&kanchan, &c, true), 40);
+
+        c.agari = crate::tile::parse_tiles("3m").unwrap()[0];
+        let penchan = kanchan_hand
+            .decompositions(&c)
+            .into_iter()
+            .find(|h| {
+                matches!(h, CompleteHand::Standard(groups, _)
+                    if groups.iter().any(|g| g.has_agari() && g.wait() == Some(Wait::Penchan)))
+            })
+            .expect("expected a decomposition with a penchan agari group");
+        assert_eq!(count_fu(&penchan, &c, true), 40);
+    }
+
+    #[test]
+    fn kazoe_yakuman_caps_at_a_single_yakuman_without_a_genuine_one() {
+        let rules = Rules::default();
+        assert_eq!(base_points(13, 20, &rules, false), 8000);
+        assert_eq!(base_points(26, 20, &rules, false), 8000);
+    }
+
+    #[test]
+    fn a_genuine_double_yakuman_still_doubles() {
+        let rules = Rules::default();
+        assert_eq!(base_points(13, 20, &rules, true), 8000);
+        assert_eq!(base_points(26, 20, &rules, true), 16000);
+    }
+
+    /// `payments` splits the same han/fu differently by who's dealing and how the win was scored:
+    /// a ron is a single payment from the discarder at a dealer-scaled multiplier, while a tsumo
+    /// splits unevenly between the dealer and the other two players, unless the winner is the
+    /// dealer themselves, in which case every other seat pays the same. Honba add a flat 300
+    /// points total either way.
+    #[test]
+    fn payments_splits_by_dealer_status_and_win_type() {
+        let rules = Rules::default();
+        assert_eq!(base_points(3, 30, &rules, false), 960);
+
+        let mut c = ctx(Tile::Wind(Direction::South), Location::Discard(Opponent::Across));
+        c.seat = Direction::South; // a non-dealer ron
+        assert_eq!(payments(3, 30, &c, &rules, false), Payments::Ron(3900));
+
+        c.seat = Direction::East; // the same hand, but the dealer rons instead
+        assert_eq!(payments(3, 30, &c, &rules, false), Payments::Ron(5800));
+
+        c.seat = Direction::South;
+        c.source = Location::LiveWall; // a non-dealer tsumo
+        assert_eq!(
+            payments(3, 30, &c, &rules, false),
+            Payments::Tsumo { from_dealer: 2000, from_non_dealer: 1000 }
+        );
+
+        c.seat = Direction::East; // the dealer tsumo pays the same rate from every seat
+        assert_eq!(
+            payments(3, 30, &c, &rules, false),
+            Payments::Tsumo { from_dealer: 2000, from_non_dealer: 2000 }
+        );
+
+        c.honba = 1;
+        assert_eq!(
+            payments(3, 30, &c, &rules, false),
+            Payments::Tsumo { from_dealer: 2100, from_non_dealer: 2100 }
+        );
+        c.source = Location::Discard(Opponent::Across);
+        assert_eq!(payments(3, 30, &c, &rules, false), Payments::Ron(6100));
+    }
+}
@@ -1,3 +1,7 @@
+use failure::format_err;
+use failure::Error;
+use std::fmt;
+
 /// The three suits of numbered tiles.
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[allow(missing_docs)]
@@ -41,16 +45,15 @@ pub enum Dragon {
     Green,
     Red,
 }
-use Dragon::*;
 
 impl Dragon {
     /// Get the next dragon tile in order, looping around. This ordering is mostly used only for
     /// dora indication.
     pub fn next(self) -> Dragon {
         match self {
-            White => Green,
-            Green => Red,
-            Red => White,
+            Dragon::White => Dragon::Green,
+            Dragon::Green => Dragon::Red,
+            Dragon::Red => Dragon::White,
         }
     }
 }
@@ -141,7 +144,7 @@ impl Tile {
     /// Returns `true` if this tile is green, qualifying it for ryūiisō.
     pub fn is_green(self) -> bool {
         match self {
-            Dragon(Green) => true,
+            Dragon(Dragon::Green) => true,
             Suited(Souzu, Val(n)) => [2, 3, 4, 6, 8].contains(&n),
             _ => false,
         }
@@ -171,6 +174,146 @@ impl Tile {
     }
 }
 
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", to_notation(&[*self]))
+    }
+}
+
+/// A tile together with the physical properties that [`Tile`] itself ignores, currently just
+/// whether it is a red five.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TileInstance {
+    /// The tile's value.
+    pub tile: Tile,
+    /// Whether this is a red five (aka dora).
+    pub red: bool,
+}
+
+impl TileInstance {
+    /// Constructs a non-red tile instance.
+    pub fn new(tile: Tile) -> TileInstance {
+        TileInstance { tile, red: false }
+    }
+}
+
+/// Counts how many of `tiles` match one of `indicators` once each is stepped forward through
+/// [`Tile::indicated_dora`].
+pub(crate) fn dora_matches(tiles: &[TileInstance], indicators: &[Tile]) -> u32 {
+    indicators
+        .iter()
+        .map(|ind| {
+            let dora = ind.indicated_dora();
+            tiles.iter().filter(|t| t.tile == dora).count() as u32
+        })
+        .sum()
+}
+
+/// Counts the han contributed by dora: every tile in `tiles` indicated by one of `indicators`,
+/// plus one additional han for every red five in `tiles`.
+pub fn count_dora(tiles: &[TileInstance], indicators: &[Tile]) -> u32 {
+    dora_matches(tiles, indicators) + tiles.iter().filter(|t| t.red).count() as u32
+}
+
+/// Writes a set of tiles using the ubiquitous `<digits><suit letter>` mahjong notation (e.g.
+/// `"123m456p77z"`): each suit's tiles are sorted and written as a run of digits followed by
+/// `m`/`p`/`s`/`z`, in manzu/pinzu/souzu/honour order, with honours numbered 1-4 for the winds
+/// E/S/W/N and 5-7 for the dragons haku/hatsu/chun.
+pub fn to_notation(tiles: &[Tile]) -> String {
+    let mut sorted = tiles.to_vec();
+    sorted.sort();
+    let mut out = String::new();
+    for suit in &[Manzu, Pinzu, Souzu] {
+        let digits: String = sorted
+            .iter()
+            .filter_map(|t| match t {
+                Suited(s, v) if s == suit => Some((b'0' + v.val()) as char),
+                _ => None,
+            })
+            .collect();
+        if !digits.is_empty() {
+            out.push_str(&digits);
+            out.push(match suit {
+                Manzu => 'm',
+                Pinzu => 'p',
+                Souzu => 's',
+            });
+        }
+    }
+    let honors: String = sorted
+        .iter()
+        .filter_map(|&t| honor_digit(t))
+        .map(|d| (b'0' + d) as char)
+        .collect();
+    if !honors.is_empty() {
+        out.push_str(&honors);
+        out.push('z');
+    }
+    out
+}
+
+fn honor_digit(t: Tile) -> Option<u8> {
+    match t {
+        Wind(East) => Some(1),
+        Wind(South) => Some(2),
+        Wind(West) => Some(3),
+        Wind(North) => Some(4),
+        Dragon(Dragon::White) => Some(5),
+        Dragon(Dragon::Green) => Some(6),
+        Dragon(Dragon::Red) => Some(7),
+        _ => None,
+    }
+}
+
+fn honor_tile(d: u8) -> Option<Tile> {
+    match d {
+        1 => Some(Wind(East)),
+        2 => Some(Wind(South)),
+        3 => Some(Wind(West)),
+        4 => Some(Wind(North)),
+        5 => Some(Dragon(Dragon::White)),
+        6 => Some(Dragon(Dragon::Green)),
+        7 => Some(Dragon(Dragon::Red)),
+        _ => None,
+    }
+}
+
+/// Parses a run of tiles written in the standard `<digits><suit letter>` notation (e.g.
+/// `"111123666789s11z"`) into an ordered list of tiles. Returns an error if a digit run is not
+/// terminated by a suit letter, or if a `z` digit falls outside 1-7.
+pub fn parse_tiles(s: &str) -> Result<Vec<Tile>, Error> {
+    let mut tiles = Vec::new();
+    let mut digits: Vec<u8> = Vec::new();
+    for c in s.chars() {
+        match c {
+            '1'..='9' => digits.push(c as u8 - b'0'),
+            'm' | 'p' | 's' | 'z' => {
+                if digits.is_empty() {
+                    return Err(format_err!("suit letter '{}' with no preceding digits", c));
+                }
+                for &d in &digits {
+                    let tile = match c {
+                        'm' => Suited(Manzu, Val::new(d)),
+                        'p' => Suited(Pinzu, Val::new(d)),
+                        's' => Suited(Souzu, Val::new(d)),
+                        'z' => honor_tile(d)
+                            .ok_or_else(|| format_err!("honour value {} out of range 1-7", d))?,
+                        _ => unreachable!(),
+                    };
+                    tiles.push(tile);
+                }
+                digits.clear();
+            }
+            _ => return Err(format_err!("unexpected character '{}' in tile notation", c)),
+        }
+    }
+    if !digits.is_empty() {
+        return Err(format_err!("digit run with no trailing suit letter"));
+    }
+    tiles.sort();
+    Ok(tiles)
+}
+
 struct AllTiles {
     next: Option<Tile>,
 }
@@ -183,13 +326,51 @@ impl Iterator for AllTiles {
         if let Some(val) = cur {
             self.next = match val {
                 Suited(Manzu, Val(9)) => Some(Suited(Souzu, Val(1))),
-                Suited(Souzu, Val(9)) => Some(Suited(Pinzu, Val(9))),
+                Suited(Souzu, Val(9)) => Some(Suited(Pinzu, Val(1))),
                 Suited(Pinzu, Val(9)) => Some(Wind(East)),
-                Wind(North) => Some(Dragon(White)),
-                Dragon(Red) => None,
+                Wind(North) => Some(Dragon(Dragon::White)),
+                Dragon(Dragon::Red) => None,
                 _ => Some(val.indicated_dora()),
             };
         }
         cur
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_yields_every_tile_type_exactly_once() {
+        let all: Vec<Tile> = Tile::all().collect();
+        assert_eq!(all.len(), 34);
+        assert_eq!(
+            all.iter().cloned().collect::<std::collections::BTreeSet<_>>().len(),
+            34
+        );
+        assert!(all.contains(&Suited(Pinzu, Val(3))));
+        assert!(all.contains(&Suited(Pinzu, Val(8))));
+    }
+
+    /// `to_notation` always writes manzu/pinzu/souzu/honour in that order regardless of the input
+    /// order, and `parse_tiles` is its inverse: feeding its own output back in reproduces the same
+    /// tiles.
+    #[test]
+    fn parse_tiles_round_trips_through_to_notation() {
+        let shuffled = parse_tiles("7z456p123m2z55s").unwrap();
+        assert_eq!(to_notation(&shuffled), "123m456p55s27z");
+        let reparsed = parse_tiles(&to_notation(&shuffled)).unwrap();
+        assert_eq!(reparsed, shuffled);
+    }
+
+    /// A digit run with no trailing suit letter, a suit letter with nothing in front of it, an
+    /// out-of-range honour digit, and any other stray character are all rejected.
+    #[test]
+    fn parse_tiles_rejects_malformed_input() {
+        assert!(parse_tiles("123").is_err());
+        assert!(parse_tiles("m123p").is_err());
+        assert!(parse_tiles("8z").is_err());
+        assert!(parse_tiles("123x").is_err());
+    }
+}